@@ -1,6 +1,10 @@
-use crate::parse::ast;
+use crate::parse::{Context, ast};
 use clap::{Parser, ValueEnum};
-use std::path::{Path, PathBuf};
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 mod c;
 mod cpp;
@@ -41,94 +45,1038 @@ impl Compile {
     }
 }
 
+/// The attributes applied to an item, field or argument.
+#[derive(Clone)]
+pub(crate) struct Attributes(Vec<ast::Attribute>);
+
+impl Attributes {
+    /// The string argument of an attribute, such as `#[name("x")]` or `#[name = "x"]`.
+    fn string(&self, name: &str) -> Option<&str> {
+        ast::find_attribute(&self.0, name)?.string()
+    }
+
+    /// If deprecated, the deprecation message, if any.
+    fn deprecated(&self) -> Option<Option<&str>> {
+        ast::find_attribute(&self.0, "deprecated").map(|a| a.string())
+    }
+
+    /// Returns true if the struct is packed.
+    fn packed(&self) -> bool {
+        ast::find_attribute(&self.0, "packed").is_some()
+    }
+
+    /// Returns true if the function is a destructor.
+    fn destructor(&self) -> bool {
+        ast::find_attribute(&self.0, "destructor").is_some()
+    }
+
+    /// The minimum alignment, if given.
+    fn align(&self) -> Option<u64> {
+        ast::find_attribute(&self.0, "align")?.integer()
+    }
+}
+
+/// Describe a deprecation, for docs.
+fn deprecation_note(attribute: &ast::Attribute, subject: &str) -> String {
+    match attribute.string() {
+        Some(message) => format!(" {} deprecated: {}", subject, message),
+        None => format!(" {} deprecated.", subject),
+    }
+}
+
+/// Docs for an item that is only deprecated in its docs, since the declarations it produces are
+/// referenced throughout the generated code.
+fn deprecated_docs(docs: &[String], attributes: &[ast::Attribute]) -> Vec<String> {
+    let mut docs = docs.to_vec();
+    if let Some(attribute) = ast::find_attribute(attributes, "deprecated") {
+        if !docs.is_empty() {
+            docs.push(String::new());
+        }
+        docs.push(deprecation_note(attribute, "This is"));
+    }
+    docs
+}
+
+/// Docs for a function, listing documented arguments and noting any deprecated arguments.
+fn function_docs(docs: &[String], function: &ast::Function) -> Vec<String> {
+    let mut docs = docs.to_vec();
+    for (index, arg) in function
+        .args
+        .iter()
+        .filter(|arg| !arg.docs.is_empty())
+        .enumerate()
+    {
+        if index == 0 && !docs.is_empty() {
+            docs.push(String::new());
+        }
+        docs.push(format!(" \\param {}{}", arg.name.identifier, arg.docs[0]));
+        docs.extend(arg.docs[1..].iter().cloned());
+    }
+    for arg in &function.args {
+        if let Some(attribute) = ast::find_attribute(&arg.attributes, "deprecated") {
+            if !docs.is_empty() {
+                docs.push(String::new());
+            }
+            let subject = format!("Argument `{}` is", arg.name.identifier);
+            docs.push(deprecation_note(attribute, &subject));
+        }
+    }
+    docs
+}
+
+#[derive(Clone)]
+pub(crate) struct Constant {
+    docs: Vec<String>,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    package: Vec<String>,
+    name: String,
+    ty: ast::Type,
+    value: i128,
+}
+
 #[derive(Clone)]
 pub(crate) struct AbstractStruct {
     docs: Vec<String>,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    package: Vec<String>,
     name: String,
 }
 
 #[derive(Clone)]
 pub(crate) struct Field {
     docs: Vec<String>,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    attributes: Attributes,
     name: String,
     ty: ast::Type,
+    /// The width in bits, if the field is a bitfield
+    width: Option<i128>,
 }
 
 #[derive(Clone)]
 pub(crate) struct Struct {
     docs: Vec<String>,
+    attributes: Attributes,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    package: Vec<String>,
     name: String,
+    base: Option<ast::Type>,
     fields: Vec<Field>,
+    layout: Option<OpaqueLayout>,
+}
+
+/// The size and alignment of a struct with private fields.
+#[derive(Clone, Copy)]
+pub(crate) struct OpaqueLayout {
+    size: i128,
+    align: i128,
+}
+
+#[derive(Clone)]
+pub(crate) struct UnionVariant {
+    docs: Vec<String>,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    name: String,
+    ty: Option<ast::Type>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Union {
+    docs: Vec<String>,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    package: Vec<String>,
+    name: String,
+    variants: Vec<UnionVariant>,
+}
+
+impl Union {
+    /// Returns true if any variant carries a payload.
+    fn has_payload(&self) -> bool {
+        self.variants.iter().any(|v| v.ty.is_some())
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Alias {
+    docs: Vec<String>,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    package: Vec<String>,
+    name: String,
+    ty: ast::Type,
+}
+
+#[derive(Clone)]
+pub(crate) struct Method {
+    docs: Vec<String>,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    name: String,
+    args: Vec<Argument>,
+    returns: Option<ast::Type>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Interface {
+    docs: Vec<String>,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    package: Vec<String>,
+    name: String,
+    methods: Vec<Method>,
+}
+
+/// A complete type definition.
+///
+/// Definitions are kept in a single list so they can be emitted in dependency order.
+#[derive(Clone)]
+pub(crate) enum Definition {
+    Struct(Struct),
+    Union(Union),
+    Interface(Interface),
+    Alias(Alias),
+}
+
+impl Definition {
+    fn name(&self) -> &str {
+        match self {
+            Self::Struct(s) => &s.name,
+            Self::Union(u) => &u.name,
+            Self::Interface(i) => &i.name,
+            Self::Alias(a) => &a.name,
+        }
+    }
+
+    fn package(&self) -> &[String] {
+        match self {
+            Self::Struct(s) => &s.package,
+            Self::Union(u) => &u.package,
+            Self::Interface(i) => &i.package,
+            Self::Alias(a) => &a.package,
+        }
+    }
+
+    fn location(&self) -> &ast::Location {
+        match self {
+            Self::Struct(s) => &s.location,
+            Self::Union(u) => &u.location,
+            Self::Interface(i) => &i.location,
+            Self::Alias(a) => &a.location,
+        }
+    }
+
+    /// Returns true if this is an alias, which names another type rather than declaring one.
+    fn is_alias(&self) -> bool {
+        matches!(self, Self::Alias(_))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Variant {
+    docs: Vec<String>,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    name: String,
+    value: i128,
+}
+
+#[derive(Clone)]
+pub(crate) struct Enum {
+    docs: Vec<String>,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    package: Vec<String>,
+    name: String,
+    repr: ast::Type,
+    variants: Vec<Variant>,
+    /// Whether this is a set of flags, whose variants are bits that may be combined
+    flags: bool,
+}
+
+#[derive(Clone)]
+pub(crate) struct Argument {
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    name: String,
+    ty: ast::Type,
 }
 
 #[derive(Clone)]
 pub(crate) struct Function {
     docs: Vec<String>,
+    attributes: Attributes,
+    /// The location of the name, for diagnostics
+    location: ast::Location,
+    package: Vec<String>,
     name: String,
-    args: Vec<(String, ast::Type)>,
+    args: Vec<Argument>,
     returns: Option<ast::Type>,
+    method_of: Option<MethodOf>,
+}
+
+/// The `impl` block containing a function.
+#[derive(Clone)]
+pub(crate) struct MethodOf {
+    ty: ast::Type,
+    /// The name of the function within the block
+    name: String,
+}
+
+/// The functions of the `impl` blocks for a type, which are methods of a handle to it.
+pub(crate) struct Impl<'a> {
+    package: &'a [String],
+    /// The name of the handle
+    name: String,
+    ty: &'a ast::Type,
+    /// The functions, other than the destructor
+    functions: Vec<&'a Function>,
+    /// The destructor, if the handle owns the pointer
+    destructor: Option<&'a Function>,
 }
 
 #[derive(Clone)]
 pub(crate) struct Items {
+    /// The docs of the file being compiled
+    docs: Vec<String>,
+    /// The package of the file being compiled
+    package: Vec<String>,
+    constants: Vec<Constant>,
     abstract_structs: Vec<AbstractStruct>,
-    structs: Vec<Struct>,
+    definitions: Vec<Definition>,
+    enums: Vec<Enum>,
     functions: Vec<Function>,
 }
 
+/// The arguments of a function or method.
+fn arguments(function: &ast::Function) -> Vec<Argument> {
+    function
+        .args
+        .iter()
+        .map(|arg| Argument {
+            location: arg.name.location.clone(),
+            name: arg.name.identifier.clone(),
+            ty: arg.ty.clone(),
+        })
+        .collect()
+}
+
+impl Function {
+    /// The linker symbol, prefixed by the package path unless given by `#[link_name]`.
+    fn symbol(&self) -> String {
+        match self.attributes.string("link_name") {
+            Some(name) => name.to_string(),
+            None => qualify(&self.package, &self.name, "_"),
+        }
+    }
+
+    /// The name within its `impl` block.
+    fn method_name(&self) -> &str {
+        self.method_of
+            .as_ref()
+            .map_or(&self.name, |method_of| &method_of.name)
+    }
+
+    /// Returns true if this is a method taking a pointer to its type as `self`.
+    fn has_self(&self) -> bool {
+        self.method_of.is_some() && self.args.first().is_some_and(|arg| arg.name == "self")
+    }
+
+    /// The arguments, other than `self`.
+    fn method_args(&self) -> &[Argument] {
+        if self.has_self() {
+            &self.args[1..]
+        } else {
+            &self.args
+        }
+    }
+}
+
+/// The name of the handle to the type of an `impl` block.
+fn handle_name(ty: &ast::Type) -> String {
+    let ast::Type::Identifier(ty) = ty else {
+        unreachable!("`impl` blocks are for named types");
+    };
+    format!("{}Handle", ast::split_qualified(&ty.identifier).1)
+}
+
+/// Join a package path and local name with the given separator.
+fn qualify(package: &[String], name: &str, separator: &str) -> String {
+    package
+        .iter()
+        .map(String::as_str)
+        .chain([name])
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Format an integer as a C or C++ literal. Values beyond the range of `long long` need an
+/// unsigned suffix, and 128-bit values are built from halves of type `uint128`.
+fn int_literal(value: i128, uint128: &str) -> String {
+    if value == i64::MIN.into() {
+        // The literal would be the positive value, which doesn't fit in a signed integer
+        format!("({} - 1)", i64::MIN + 1)
+    } else if i64::try_from(value).is_ok() {
+        value.to_string()
+    } else if u64::try_from(value).is_ok() {
+        format!("{}ull", value)
+    } else {
+        // There are no 128-bit literals, so build the two's complement value from halves
+        let bits = value as u128;
+        format!(
+            "(({})0x{:x}ull << 64 | 0x{:x}ull)",
+            uint128,
+            bits >> 64,
+            bits as u64
+        )
+    }
+}
+
+impl Items {
+    /// The `impl` blocks of each type, in order of their first function.
+    fn impls(&self) -> Vec<Impl<'_>> {
+        let mut impls: Vec<Impl<'_>> = Vec::new();
+        for f in &self.functions {
+            let Some(method_of) = &f.method_of else {
+                continue;
+            };
+            let name = handle_name(&method_of.ty);
+            let index = match impls
+                .iter()
+                .position(|i| i.package == f.package && i.name == name)
+            {
+                Some(index) => index,
+                None => {
+                    impls.push(Impl {
+                        package: &f.package,
+                        name,
+                        ty: &method_of.ty,
+                        functions: Vec::new(),
+                        destructor: None,
+                    });
+                    impls.len() - 1
+                }
+            };
+            if f.attributes.destructor() {
+                impls[index].destructor = Some(f);
+            } else {
+                impls[index].functions.push(f);
+            }
+        }
+        impls
+    }
+
+    /// The package and name of the handle owning an owned pointer, if its type has a destructor.
+    fn owning_handle(&self, ty: &ast::Type) -> Option<(&[String], String)> {
+        let ast::Type::Owned(owned) = ty else {
+            return None;
+        };
+        let ast::Type::Pointer(pointer) = owned.ty.as_ref() else {
+            return None;
+        };
+        let ast::Type::Identifier(pointee) = pointer.ty.as_ref() else {
+            return None;
+        };
+        self.functions
+            .iter()
+            .filter(|f| f.attributes.destructor())
+            .find_map(|f| {
+                let method_of = f.method_of.as_ref()?;
+                let ast::Type::Identifier(ty) = &method_of.ty else {
+                    return None;
+                };
+                (ty.identifier == pointee.identifier)
+                    .then(|| (f.package.as_slice(), handle_name(&method_of.ty)))
+            })
+    }
+
+    /// Look up an enum by qualified name.
+    fn get_enum(&self, name: &str) -> Option<&Enum> {
+        self.enums
+            .iter()
+            .find(|e| qualify(&e.package, &e.name, ".") == name)
+    }
+
+    /// Look up an alias by qualified name.
+    fn get_alias(&self, name: &str) -> Option<&Alias> {
+        self.definitions.iter().find_map(|d| match d {
+            Definition::Alias(a) if qualify(&a.package, &a.name, ".") == name => Some(a),
+            _ => None,
+        })
+    }
+
+    /// Look up the struct named by a type, looking through aliases.
+    fn get_struct(&self, ty: &ast::Type) -> Option<&Struct> {
+        let ast::Type::Identifier(i) = ty else {
+            return None;
+        };
+        match self
+            .definitions
+            .iter()
+            .find(|d| qualify(d.package(), d.name(), ".") == i.identifier)?
+        {
+            Definition::Struct(s) => Some(s),
+            Definition::Alias(a) => self.get_struct(&a.ty),
+            Definition::Union(_) | Definition::Interface(_) => None,
+        }
+    }
+
+    /// Returns true if this type is a struct with a base, possibly through aliases.
+    fn is_derived(&self, ty: &ast::Type) -> bool {
+        self.get_struct(ty).is_some_and(|s| s.base.is_some())
+    }
+
+    /// Returns true if this type is `void`, possibly through aliases.
+    fn is_void(&self, ty: &ast::Type) -> bool {
+        match ty {
+            ast::Type::Void { .. } => true,
+            ast::Type::Identifier(i) => self
+                .get_alias(&i.identifier)
+                .is_some_and(|a| self.is_void(&a.ty)),
+            _ => false,
+        }
+    }
+
+    /// Returns true if this type is an array, possibly through aliases.
+    fn is_array(&self, ty: &ast::Type) -> bool {
+        match ty {
+            ast::Type::Array(_) => true,
+            ast::Type::Identifier(i) => self
+                .get_alias(&i.identifier)
+                .is_some_and(|a| self.is_array(&a.ty)),
+            _ => false,
+        }
+    }
+
+    /// Rename anything named by a keyword of the target language, by appending an underscore.
+    ///
+    /// Enum variants are only renamed if `escape_variants` is set, since some languages always
+    /// prefix them. Functions can't be renamed, since they are named by their symbol, so they are
+    /// reported as errors instead, as are names that would be renamed to a name already in use.
+    fn escape_keywords(
+        &mut self,
+        context: &mut Context,
+        language: &str,
+        keywords: &[&str],
+        escape_variants: bool,
+    ) -> Result<(), ()> {
+        self.check_escapes(context, language, keywords, escape_variants)?;
+
+        let escape = |name: &mut String| {
+            if keywords.contains(&name.as_str()) {
+                name.push('_');
+            }
+        };
+        let escape_type = |ty: &mut ast::Type| {
+            let mut identifiers = Vec::new();
+            ty.identifiers_mut(&mut identifiers);
+            for identifier in identifiers {
+                let (mut package, name) = ast::split_qualified(&identifier.identifier);
+                let mut name = name.to_string();
+                escape(&mut name);
+                package.push(name);
+                identifier.identifier = package.join(".");
+            }
+        };
+        for c in &mut self.constants {
+            escape(&mut c.name);
+            escape_type(&mut c.ty);
+        }
+        for s in &mut self.abstract_structs {
+            escape(&mut s.name);
+        }
+        for d in &mut self.definitions {
+            match d {
+                Definition::Struct(s) => {
+                    escape(&mut s.name);
+                    s.base.iter_mut().for_each(escape_type);
+                    for field in &mut s.fields {
+                        escape(&mut field.name);
+                        escape_type(&mut field.ty);
+                    }
+                }
+                Definition::Union(u) => {
+                    escape(&mut u.name);
+                    for variant in &mut u.variants {
+                        escape(&mut variant.name);
+                        variant.ty.iter_mut().for_each(escape_type);
+                    }
+                }
+                Definition::Interface(i) => {
+                    escape(&mut i.name);
+                    for method in &mut i.methods {
+                        escape(&mut method.name);
+                        for arg in &mut method.args {
+                            escape(&mut arg.name);
+                            escape_type(&mut arg.ty);
+                        }
+                        method.returns.iter_mut().for_each(escape_type);
+                    }
+                }
+                Definition::Alias(a) => {
+                    escape(&mut a.name);
+                    escape_type(&mut a.ty);
+                }
+            }
+        }
+        for e in &mut self.enums {
+            escape(&mut e.name);
+            if escape_variants {
+                e.variants.iter_mut().for_each(|v| escape(&mut v.name));
+            }
+        }
+
+        let mut result = Ok(());
+        for f in &mut self.functions {
+            for arg in &mut f.args {
+                escape(&mut arg.name);
+                escape_type(&mut arg.ty);
+            }
+            f.returns.iter_mut().for_each(escape_type);
+            if let Some(method_of) = &mut f.method_of {
+                escape(&mut method_of.name);
+                escape_type(&mut method_of.ty);
+            }
+
+            for name in [f.name.clone(), f.symbol()] {
+                if keywords.contains(&name.as_str()) {
+                    context.report(
+                        &Diagnostic::error()
+                            .with_message(format!(
+                                "function `{}` is a keyword in {}",
+                                name, language
+                            ))
+                            .with_labels(vec![Label::primary(
+                                f.location.file_id,
+                                f.location.span.clone(),
+                            )])
+                            .with_notes(vec![
+                                "functions are named by their symbol, so they can't be renamed"
+                                    .to_string(),
+                            ]),
+                    );
+                    result = Err(());
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    /// Check that no name is escaped to a name that is already used in the same scope.
+    fn check_escapes(
+        &self,
+        context: &mut Context,
+        language: &str,
+        keywords: &[&str],
+        escape_variants: bool,
+    ) -> Result<(), ()> {
+        let mut scopes: Vec<Vec<(&str, &ast::Location)>> = Vec::new();
+        let mut packages: HashMap<&[String], Vec<(&str, &ast::Location)>> = HashMap::new();
+        let mut impls: HashMap<String, Vec<(&str, &ast::Location)>> = HashMap::new();
+        for c in &self.constants {
+            packages
+                .entry(&c.package)
+                .or_default()
+                .push((&c.name, &c.location));
+        }
+        for s in &self.abstract_structs {
+            packages
+                .entry(&s.package)
+                .or_default()
+                .push((&s.name, &s.location));
+        }
+        for d in &self.definitions {
+            packages
+                .entry(d.package())
+                .or_default()
+                .push((d.name(), d.location()));
+            match d {
+                Definition::Struct(s) => {
+                    scopes.push(
+                        s.fields
+                            .iter()
+                            .map(|f| (f.name.as_str(), &f.location))
+                            .collect(),
+                    );
+                }
+                Definition::Union(u) => {
+                    scopes.push(
+                        u.variants
+                            .iter()
+                            .map(|v| (v.name.as_str(), &v.location))
+                            .collect(),
+                    );
+                }
+                Definition::Interface(i) => {
+                    scopes.push(
+                        i.methods
+                            .iter()
+                            .map(|m| (m.name.as_str(), &m.location))
+                            .collect(),
+                    );
+                    for method in &i.methods {
+                        scopes.push(
+                            method
+                                .args
+                                .iter()
+                                .map(|a| (a.name.as_str(), &a.location))
+                                .collect(),
+                        );
+                    }
+                }
+                Definition::Alias(_) => {}
+            }
+        }
+        for e in &self.enums {
+            packages
+                .entry(&e.package)
+                .or_default()
+                .push((&e.name, &e.location));
+            if escape_variants {
+                scopes.push(
+                    e.variants
+                        .iter()
+                        .map(|v| (v.name.as_str(), &v.location))
+                        .collect(),
+                );
+            }
+        }
+        for f in &self.functions {
+            packages
+                .entry(&f.package)
+                .or_default()
+                .push((&f.name, &f.location));
+            scopes.push(
+                f.args
+                    .iter()
+                    .map(|a| (a.name.as_str(), &a.location))
+                    .collect(),
+            );
+            if let Some(method_of) = &f.method_of {
+                let name = qualify(&f.package, &handle_name(&method_of.ty), ".");
+                impls
+                    .entry(name)
+                    .or_default()
+                    .push((&method_of.name, &f.location));
+            }
+        }
+        scopes.extend(packages.into_values());
+        scopes.extend(impls.into_values());
+
+        let mut collisions = scopes
+            .iter()
+            .flat_map(|scope| {
+                scope.iter().filter_map(|(name, location)| {
+                    if !keywords.contains(name) {
+                        return None;
+                    }
+                    let escaped = format!("{}_", name);
+                    let (_, other) = scope.iter().find(|(other, _)| *other == escaped)?;
+                    Some((*name, escaped, *location, *other))
+                })
+            })
+            .collect::<Vec<_>>();
+        collisions.sort_by_key(|(_, _, location, _)| (location.file_id, location.span.start));
+        for (name, escaped, location, other) in &collisions {
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!(
+                        "`{}` is a keyword in {}, and can't be renamed to `{}`",
+                        name, language, escaped
+                    ))
+                    .with_labels(vec![
+                        Label::primary(location.file_id, location.span.clone())
+                            .with_message("keyword used here"),
+                        Label::secondary(other.file_id, other.span.clone())
+                            .with_message(format!("`{}` is already used here", escaped)),
+                    ]),
+            );
+        }
+        if collisions.is_empty() {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    /// Replace qualified names with flat names, for languages without namespaces.
+    ///
+    /// Functions are always named by their symbol.
+    fn flatten_names(&mut self, flatten: impl Fn(&[String], &str) -> String) {
+        let flatten_name = |package: &mut Vec<String>, name: &mut String| {
+            *name = flatten(package, name);
+            package.clear();
+        };
+        let flatten_type = |ty: &mut ast::Type| {
+            let mut identifiers = Vec::new();
+            ty.identifiers_mut(&mut identifiers);
+            for identifier in identifiers {
+                let (package, name) = ast::split_qualified(&identifier.identifier);
+                identifier.identifier = flatten(&package, name);
+            }
+        };
+        for c in &mut self.constants {
+            flatten_name(&mut c.package, &mut c.name);
+        }
+        for s in &mut self.abstract_structs {
+            flatten_name(&mut s.package, &mut s.name);
+        }
+        for d in &mut self.definitions {
+            match d {
+                Definition::Struct(s) => {
+                    flatten_name(&mut s.package, &mut s.name);
+                    s.base.iter_mut().for_each(flatten_type);
+                    s.fields.iter_mut().for_each(|f| flatten_type(&mut f.ty));
+                }
+                Definition::Union(u) => {
+                    flatten_name(&mut u.package, &mut u.name);
+                    u.variants
+                        .iter_mut()
+                        .filter_map(|v| v.ty.as_mut())
+                        .for_each(flatten_type);
+                }
+                Definition::Interface(i) => {
+                    flatten_name(&mut i.package, &mut i.name);
+                    for method in &mut i.methods {
+                        method
+                            .args
+                            .iter_mut()
+                            .for_each(|arg| flatten_type(&mut arg.ty));
+                        method.returns.iter_mut().for_each(flatten_type);
+                    }
+                }
+                Definition::Alias(a) => {
+                    flatten_name(&mut a.package, &mut a.name);
+                    flatten_type(&mut a.ty);
+                }
+            }
+        }
+        for e in &mut self.enums {
+            flatten_name(&mut e.package, &mut e.name);
+        }
+        for f in &mut self.functions {
+            f.name = f.symbol();
+            f.package.clear();
+            f.args.iter_mut().for_each(|arg| flatten_type(&mut arg.ty));
+            f.returns.iter_mut().for_each(flatten_type);
+            if let Some(method_of) = &mut f.method_of {
+                flatten_type(&mut method_of.ty);
+            }
+        }
+    }
+}
+
 pub fn compile(compile: Compile) -> Result<(), ()> {
     let (mut context, file) = crate::parse::parse_program(&compile.file)?;
+    let docs = file.docs.clone();
+    let package = file
+        .package
+        .as_ref()
+        .map(|p| p.identifier.split('.').map(String::from).collect())
+        .unwrap_or_default();
     let items = crate::type_check::type_check(&mut context, file)?;
+    let constants = items
+        .iter()
+        .filter_map(|i| match &i.item {
+            ast::ItemType::Constant(c) => {
+                let (package, name) = ast::split_qualified(&c.name.identifier);
+                Some(Constant {
+                    docs: deprecated_docs(&i.docs, &i.attributes),
+                    location: c.name.location.clone(),
+                    package,
+                    name: name.to_string(),
+                    ty: c.ty.clone(),
+                    value: c.value.value().expect("constants are folded"),
+                })
+            }
+            _ => None,
+        })
+        .collect();
     let abstract_structs = items
         .iter()
         .filter_map(|i| match &i.item {
-            ast::ItemType::Struct(s) if s.fields.is_none() => Some(AbstractStruct {
-                docs: i.docs.clone(),
-                name: s.name.identifier.clone(),
-            }),
+            ast::ItemType::Struct(s) if s.fields.is_none() && s.layout.is_none() => {
+                let (package, name) = ast::split_qualified(&s.name.identifier);
+                Some(AbstractStruct {
+                    docs: deprecated_docs(&i.docs, &i.attributes),
+                    location: s.name.location.clone(),
+                    package,
+                    name: name.to_string(),
+                })
+            }
             _ => None,
         })
         .collect();
-    let structs = items
+    let definitions = items
         .iter()
         .filter_map(|i| match &i.item {
-            ast::ItemType::Struct(s) if s.fields.is_some() => Some(Struct {
-                docs: i.docs.clone(),
-                name: s.name.identifier.clone(),
-                fields: s
-                    .fields
-                    .as_ref()
-                    .unwrap()
-                    .iter()
-                    .map(|field| Field {
-                        docs: field.docs.clone(),
-                        name: field.name.identifier.clone(),
-                        ty: field.ty.clone(),
-                    })
-                    .collect(),
-            }),
+            ast::ItemType::Struct(s) if s.fields.is_some() || s.layout.is_some() => {
+                let (package, name) = ast::split_qualified(&s.name.identifier);
+                Some(Definition::Struct(Struct {
+                    docs: deprecated_docs(&i.docs, &i.attributes),
+                    attributes: Attributes(i.attributes.clone()),
+                    location: s.name.location.clone(),
+                    package,
+                    name: name.to_string(),
+                    base: s.base.clone(),
+                    fields: s
+                        .fields
+                        .iter()
+                        .flatten()
+                        .map(|field| Field {
+                            docs: field.docs.clone(),
+                            location: field.name.location.clone(),
+                            attributes: Attributes(field.attributes.clone()),
+                            name: field.name.identifier.clone(),
+                            ty: field.ty.clone(),
+                            width: field
+                                .width
+                                .as_ref()
+                                .map(|width| width.value().expect("widths are folded")),
+                        })
+                        .collect(),
+                    layout: s.layout.as_ref().map(|layout| OpaqueLayout {
+                        size: layout.size.value().expect("sizes are folded"),
+                        align: layout.align.value().expect("alignments are folded"),
+                    }),
+                }))
+            }
+            ast::ItemType::Union(u) => {
+                let (package, name) = ast::split_qualified(&u.name.identifier);
+                Some(Definition::Union(Union {
+                    docs: deprecated_docs(&i.docs, &i.attributes),
+                    location: u.name.location.clone(),
+                    package,
+                    name: name.to_string(),
+                    variants: u
+                        .variants
+                        .iter()
+                        .map(|variant| UnionVariant {
+                            docs: variant.docs.clone(),
+                            location: variant.name.location.clone(),
+                            name: variant.name.identifier.clone(),
+                            ty: variant.ty.clone(),
+                        })
+                        .collect(),
+                }))
+            }
+            ast::ItemType::Interface(interface) => {
+                let (package, name) = ast::split_qualified(&interface.name.identifier);
+                Some(Definition::Interface(Interface {
+                    docs: deprecated_docs(&i.docs, &i.attributes),
+                    location: interface.name.location.clone(),
+                    package,
+                    name: name.to_string(),
+                    methods: interface
+                        .methods
+                        .iter()
+                        .map(|method| Method {
+                            docs: function_docs(&method.docs, &method.function),
+                            location: method.function.name.location.clone(),
+                            name: method.function.name.identifier.clone(),
+                            args: arguments(&method.function),
+                            returns: method.function.returns.as_ref().map(|r| r.as_ref().clone()),
+                        })
+                        .collect(),
+                }))
+            }
+            ast::ItemType::Alias(a) => {
+                let (package, name) = ast::split_qualified(&a.name.identifier);
+                Some(Definition::Alias(Alias {
+                    docs: deprecated_docs(&i.docs, &i.attributes),
+                    location: a.name.location.clone(),
+                    package,
+                    name: name.to_string(),
+                    ty: a.ty.clone(),
+                }))
+            }
+            _ => None,
+        })
+        .collect();
+    let enums = items
+        .iter()
+        .filter_map(|i| match &i.item {
+            ast::ItemType::Enum(e) => {
+                let (package, name) = ast::split_qualified(&e.name.identifier);
+                Some(Enum {
+                    docs: deprecated_docs(&i.docs, &i.attributes),
+                    location: e.name.location.clone(),
+                    package,
+                    name: name.to_string(),
+                    repr: e.repr.clone(),
+                    variants: e
+                        .variants
+                        .iter()
+                        .zip(e.discriminants())
+                        .map(|(variant, value)| Variant {
+                            docs: variant.docs.clone(),
+                            location: variant.name.location.clone(),
+                            name: variant.name.identifier.clone(),
+                            value,
+                        })
+                        .collect(),
+                    flags: false,
+                })
+            }
+            ast::ItemType::Flags(f) => {
+                let (package, name) = ast::split_qualified(&f.name.identifier);
+                Some(Enum {
+                    docs: deprecated_docs(&i.docs, &i.attributes),
+                    location: f.name.location.clone(),
+                    package,
+                    name: name.to_string(),
+                    repr: f.repr.clone(),
+                    variants: f
+                        .flags
+                        .iter()
+                        .map(|flag| Variant {
+                            docs: flag.docs.clone(),
+                            location: flag.name.location.clone(),
+                            name: flag.name.identifier.clone(),
+                            value: flag.value.value().expect("flags are folded"),
+                        })
+                        .collect(),
+                    flags: true,
+                })
+            }
             _ => None,
         })
         .collect();
     let functions = items
         .iter()
         .filter_map(|i| match &i.item {
-            ast::ItemType::Function(f) => Some(Function {
-                docs: i.docs.clone(),
-                name: f.name.identifier.clone(),
-                args: f
-                    .args
-                    .iter()
-                    .map(|(name, ty)| (name.identifier.clone(), ty.clone()))
-                    .collect(),
-                returns: f.returns.as_ref().map(|r| r.as_ref().clone()),
-            }),
+            ast::ItemType::Function(f) => {
+                let (package, name) = ast::split_qualified(&f.name.identifier);
+                Some(Function {
+                    docs: function_docs(&i.docs, f),
+                    attributes: Attributes(i.attributes.clone()),
+                    location: f.name.location.clone(),
+                    package,
+                    name: name.to_string(),
+                    args: arguments(f),
+                    returns: f.returns.as_ref().map(|r| r.as_ref().clone()),
+                    method_of: f.method_of.as_ref().map(|method_of| MethodOf {
+                        ty: method_of.ty.clone(),
+                        name: method_of.name.identifier.clone(),
+                    }),
+                })
+            }
             _ => None,
         })
         .collect();
     let items = Items {
+        docs,
+        package,
+        constants,
         abstract_structs,
-        structs,
+        definitions,
+        enums,
         functions,
     };
     match compile.language {
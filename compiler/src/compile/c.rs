@@ -1,9 +1,9 @@
-use super::{Compile, Items, Mode};
+use super::{Attributes, Compile, Definition, Items, Method, Mode, int_literal, qualify};
 use crate::parse::{Context, ast};
 use askama::Template;
 use codespan_reporting::diagnostic::Diagnostic;
 use std::{
-    collections::BTreeSet,
+    collections::HashSet,
     fs::OpenOptions,
     io::{Error, Write},
     path::{Path, PathBuf},
@@ -11,14 +11,16 @@ use std::{
 
 // C backend: generates concrete C typedefs and prototypes.
 // Differs from C++ (templates/RAII) and Python (ctypes) by materializing
-// per-type slice/owned typedefs because C has no templates.
+// per-type slice/owned/shared/optional/closure typedefs because C has no
+// templates.
 #[derive(Template)]
 #[template(path = "c/import.h", escape = "none")]
 struct CHeaderTemplate {
     items: Items,
-    slice_decls: Vec<SliceDecl>,
-    owned_ptr_decls: Vec<OwnedPtrDecl>,
-    owned_slice_decls: Vec<OwnedSliceDecl>,
+    // Typedefs to emit before each definition, in the same order as `items.definitions`
+    preludes: Vec<Vec<TypeDecl>>,
+    // Typedefs only needed by function prototypes
+    function_type_decls: Vec<TypeDecl>,
 }
 
 #[derive(Template)]
@@ -32,7 +34,7 @@ struct CExportInlineTemplate {
 #[derive(Clone)]
 struct SliceDecl {
     name: String,
-    elem_c_type: String,
+    data_decl: String,
 }
 #[derive(Clone)]
 struct OwnedPtrDecl {
@@ -44,8 +46,101 @@ struct OwnedSliceDecl {
     name: String,
     slice_name: String,
 }
+#[derive(Clone)]
+struct SharedPtrDecl {
+    name: String,
+    data_c_type: String,
+}
+#[derive(Clone)]
+struct ClosureDecl {
+    name: String,
+    call_decl: String,
+}
+#[derive(Clone)]
+struct OptionalDecl {
+    name: String,
+    value_decl: String,
+}
+#[derive(Clone)]
+enum TypeDecl {
+    Slice(SliceDecl),
+    OwnedPtr(OwnedPtrDecl),
+    OwnedSlice(OwnedSliceDecl),
+    SharedPtr(SharedPtrDecl),
+    Closure(ClosureDecl),
+    BorrowedClosure(ClosureDecl),
+    Optional(OptionalDecl),
+}
+
+/// Reserved words of C23, including those spelled as macros in earlier standards.
+const KEYWORDS: &[&str] = &[
+    "alignas",
+    "alignof",
+    "auto",
+    "bool",
+    "break",
+    "case",
+    "char",
+    "const",
+    "constexpr",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "nullptr",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "struct",
+    "switch",
+    "thread_local",
+    "true",
+    "typedef",
+    "typeof",
+    "typeof_unqual",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "_Alignas",
+    "_Alignof",
+    "_Atomic",
+    "_BitInt",
+    "_Bool",
+    "_Complex",
+    "_Decimal128",
+    "_Decimal32",
+    "_Decimal64",
+    "_Generic",
+    "_Imaginary",
+    "_Noreturn",
+    "_Static_assert",
+    "_Thread_local",
+];
+
+pub(super) fn compile(context: &mut Context, options: Compile, mut items: Items) -> Result<(), ()> {
+    // C has a single namespace, so packages become name prefixes
+    items.flatten_names(|package, name| qualify(package, name, "_"));
+    // Enum variants are always prefixed by the enum name
+    items.escape_keywords(context, "C", KEYWORDS, false)?;
 
-pub(super) fn compile(context: &mut Context, options: Compile, items: Items) -> Result<(), ()> {
     let file_stem = Path::new(&options.file)
         .file_stem()
         .unwrap()
@@ -89,7 +184,7 @@ fn output_path(output_dir: Option<&PathBuf>, file_name: &str) -> PathBuf {
 }
 
 fn compile_header(items: Items, output_file: &Path) -> Result<(), Error> {
-    let (slice_decls, owned_ptr_decls, owned_slice_decls) = collect_type_decls(&items);
+    let (preludes, function_type_decls) = collect_type_decls(&items);
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
@@ -98,9 +193,8 @@ fn compile_header(items: Items, output_file: &Path) -> Result<(), Error> {
 
     let template = CHeaderTemplate {
         items,
-        slice_decls,
-        owned_ptr_decls,
-        owned_slice_decls,
+        preludes,
+        function_type_decls,
     };
     write!(file, "{}", template.render().unwrap())?;
     Ok(())
@@ -122,20 +216,31 @@ fn compile_export_inline(
     Ok(())
 }
 
-// Gather all slice/owned types reachable in the ABI and produce concrete
-// typedef names for the C header.
-fn collect_type_decls(items: &Items) -> (Vec<SliceDecl>, Vec<OwnedPtrDecl>, Vec<OwnedSliceDecl>) {
+// Gather all slice/owned/shared/optional/closure types reachable in the ABI and
+// produce concrete typedef names for the C header.
+//
+// Typedefs are grouped by the definition that first needs them, so they can
+// be emitted just before it (definitions may alias or contain fat pointers,
+// and fat pointers may point to aliases). Anything only needed by functions
+// is returned separately.
+fn collect_type_decls(items: &Items) -> (Vec<Vec<TypeDecl>>, Vec<TypeDecl>) {
     struct Collector {
-        slice: BTreeSet<(String, String)>,
-        owned_ptr: BTreeSet<(String, String)>,
-        owned_slice: BTreeSet<(String, String)>,
+        seen: HashSet<String>,
+        decls: Vec<TypeDecl>,
     }
     impl Collector {
-        fn new() -> Self {
-            Self {
-                slice: BTreeSet::new(),
-                owned_ptr: BTreeSet::new(),
-                owned_slice: BTreeSet::new(),
+        fn push(&mut self, decl: TypeDecl) {
+            let name = match &decl {
+                TypeDecl::Slice(d) => format!("vellum_slice_{}", d.name),
+                TypeDecl::OwnedPtr(d) => format!("vellum_owned_ptr_{}", d.name),
+                TypeDecl::OwnedSlice(d) => format!("vellum_owned_slice_{}", d.name),
+                TypeDecl::SharedPtr(d) => format!("vellum_shared_ptr_{}", d.name),
+                TypeDecl::Closure(d) => format!("vellum_closure_{}", d.name),
+                TypeDecl::BorrowedClosure(d) => format!("vellum_borrowed_closure_{}", d.name),
+                TypeDecl::Optional(d) => format!("vellum_optional_{}", d.name),
+            };
+            if self.seen.insert(name) {
+                self.decls.push(decl);
             }
         }
         fn add(&mut self, ty: &ast::Type) {
@@ -150,8 +255,10 @@ fn collect_type_decls(items: &Items) -> (Vec<SliceDecl>, Vec<OwnedPtrDecl>, Vec<
                         },
                         mangle_type(&s.ty)
                     );
-                    let elem = c_type(&s.ty, matches!(s.modifier, ast::PointerModifier::Const));
-                    self.slice.insert((name, elem));
+                    self.push(TypeDecl::Slice(SliceDecl {
+                        name,
+                        data_decl: slice_data_decl(s),
+                    }));
                 }
                 ast::Type::Owned(o) => {
                     match o.ty.as_ref() {
@@ -165,30 +272,73 @@ fn collect_type_decls(items: &Items) -> (Vec<SliceDecl>, Vec<OwnedPtrDecl>, Vec<
                                 },
                                 mangle_type(&s.ty)
                             );
-                            let elem =
-                                c_type(&s.ty, matches!(s.modifier, ast::PointerModifier::Const));
-                            self.slice.insert((name.clone(), elem));
-                            self.owned_slice.insert((name, String::new()));
+                            self.push(TypeDecl::OwnedSlice(OwnedSliceDecl {
+                                slice_name: format!("vellum_slice_{}", name),
+                                name,
+                            }));
+                        }
+                        ast::Type::Pointer(p) => {
+                            // owned pointer data type is the underlying C pointer type,
+                            // which may be null
+                            let data_ty = pointer_c_type(p, "VELLUM_NULLABLE");
+                            let name = mangle_type(&o.ty);
+                            self.push(TypeDecl::OwnedPtr(OwnedPtrDecl {
+                                name,
+                                data_c_type: data_ty,
+                            }));
                         }
                         _ => {
                             // owned pointer data type is the underlying C pointer type
                             let data_ty = DisplayTypeC(&o.ty).to_string();
-                            let name = format!("{}", mangle_type(&o.ty));
-                            self.owned_ptr.insert((name, data_ty));
+                            let name = mangle_type(&o.ty);
+                            self.push(TypeDecl::OwnedPtr(OwnedPtrDecl {
+                                name,
+                                data_c_type: data_ty,
+                            }));
+                        }
+                    }
+                }
+                ast::Type::Shared(sh) => {
+                    if let ast::Type::Pointer(p) = sh.ty.as_ref() {
+                        // like owned pointers, the data may be null
+                        self.push(TypeDecl::SharedPtr(SharedPtrDecl {
+                            name: mangle_type(&sh.ty),
+                            data_c_type: pointer_c_type(p, "VELLUM_NULLABLE"),
+                        }));
+                    }
+                }
+                ast::Type::FunctionPointer(fp) => {
+                    let decl = ClosureDecl {
+                        name: mangle_signature(fp),
+                        call_decl: closure_call_decl(fp),
+                    };
+                    match fp.fn_ty {
+                        ast::FunctionType::Function => {}
+                        ast::FunctionType::Closure => self.push(TypeDecl::Closure(decl)),
+                        ast::FunctionType::BorrowedClosure => {
+                            self.push(TypeDecl::BorrowedClosure(decl))
                         }
                     }
                 }
+                ast::Type::Optional(o) => {
+                    self.push(TypeDecl::Optional(OptionalDecl {
+                        name: mangle_type(&o.ty),
+                        value_decl: declare(&o.ty, "value"),
+                    }));
+                }
                 _ => {}
             }
         }
     }
+    // Visit children first, so nested typedefs are declared before their users
     fn visit_type_rec(col: &mut Collector, ty: &ast::Type) {
-        col.add(ty);
         match ty {
-            ast::Type::Pointer(p) => visit_type_rec(col, &p.ty),
+            ast::Type::Pointer(p) | ast::Type::OptionalPointer(p) => visit_type_rec(col, &p.ty),
             ast::Type::Slice(s) => visit_type_rec(col, &s.ty),
             ast::Type::Owned(o) => visit_type_rec(col, &o.ty),
+            ast::Type::Shared(s) => visit_type_rec(col, &s.ty),
             ast::Type::Array(a) => visit_type_rec(col, &a.ty),
+            ast::Type::Optional(o) => visit_type_rec(col, &o.ty),
             ast::Type::FunctionPointer(fp) => {
                 if let Some(r) = &fp.returns {
                     visit_type_rec(col, r);
@@ -199,46 +349,48 @@ fn collect_type_decls(items: &Items) -> (Vec<SliceDecl>, Vec<OwnedPtrDecl>, Vec<
             }
             _ => {}
         }
+        col.add(ty);
     }
-    let mut col = Collector::new();
-    for s in &items.structs {
-        for f in &s.fields {
-            visit_type_rec(&mut col, &f.ty);
+    let mut col = Collector {
+        seen: HashSet::new(),
+        decls: Vec::new(),
+    };
+    let mut preludes = Vec::new();
+    for d in &items.definitions {
+        match d {
+            Definition::Struct(s) => {
+                for f in &s.fields {
+                    visit_type_rec(&mut col, &f.ty);
+                }
+            }
+            Definition::Union(u) => {
+                for t in u.variants.iter().filter_map(|v| v.ty.as_ref()) {
+                    visit_type_rec(&mut col, t);
+                }
+            }
+            Definition::Interface(i) => {
+                for method in &i.methods {
+                    for arg in &method.args {
+                        visit_type_rec(&mut col, &arg.ty);
+                    }
+                    if let Some(r) = &method.returns {
+                        visit_type_rec(&mut col, r);
+                    }
+                }
+            }
+            Definition::Alias(a) => visit_type_rec(&mut col, &a.ty),
         }
+        preludes.push(std::mem::take(&mut col.decls));
     }
     for f in &items.functions {
-        for (_, t) in &f.args {
-            visit_type_rec(&mut col, t);
+        for arg in &f.args {
+            visit_type_rec(&mut col, &arg.ty);
         }
         if let Some(r) = &f.returns {
             visit_type_rec(&mut col, r);
         }
     }
-    let slice_decls = col
-        .slice
-        .into_iter()
-        .map(|(name, elem)| SliceDecl {
-            name,
-            elem_c_type: elem,
-        })
-        .collect();
-    let owned_ptr_decls = col
-        .owned_ptr
-        .into_iter()
-        .map(|(name, data)| OwnedPtrDecl {
-            name,
-            data_c_type: data,
-        })
-        .collect();
-    let owned_slice_decls = col
-        .owned_slice
-        .into_iter()
-        .map(|(name, _)| OwnedSliceDecl {
-            name: name.clone(),
-            slice_name: format!("vellum_slice_{}", name),
-        })
-        .collect();
-    (slice_decls, owned_ptr_decls, owned_slice_decls)
+    (preludes, col.decls)
 }
 
 fn mangle_type(ty: &ast::Type) -> String {
@@ -249,13 +401,22 @@ fn mangle_type(ty: &ast::Type) -> String {
             ast::Primitive::I16 => "i16".into(),
             ast::Primitive::I32 => "i32".into(),
             ast::Primitive::I64 => "i64".into(),
+            ast::Primitive::I128 => "i128".into(),
             ast::Primitive::Isize => "isize".into(),
             ast::Primitive::U8 => "u8".into(),
             ast::Primitive::U16 => "u16".into(),
             ast::Primitive::U32 => "u32".into(),
             ast::Primitive::U64 => "u64".into(),
+            ast::Primitive::U128 => "u128".into(),
             ast::Primitive::Usize => "usize".into(),
+            ast::Primitive::CChar => "c_char".into(),
+            ast::Primitive::CInt => "c_int".into(),
+            ast::Primitive::CLong => "c_long".into(),
+            ast::Primitive::CUlong => "c_ulong".into(),
+            ast::Primitive::F32 => "f32".into(),
+            ast::Primitive::F64 => "f64".into(),
         },
+        ast::Type::Void { .. } => "void".into(),
         ast::Type::Pointer(p) => {
             let base = mangle_type(&p.ty);
             match p.modifier {
@@ -263,6 +424,13 @@ fn mangle_type(ty: &ast::Type) -> String {
                 ast::PointerModifier::Mut => format!("{}_ptr", base),
             }
         }
+        ast::Type::OptionalPointer(p) => {
+            let base = mangle_type(&p.ty);
+            match p.modifier {
+                ast::PointerModifier::Const => format!("const_{}_optional_ptr", base),
+                ast::PointerModifier::Mut => format!("{}_optional_ptr", base),
+            }
+        }
         ast::Type::String(s) => match s.modifier {
             ast::PointerModifier::Const => "const_char_ptr".into(),
             ast::PointerModifier::Mut => "char_ptr".into(),
@@ -275,28 +443,41 @@ fn mangle_type(ty: &ast::Type) -> String {
             }
         }
         ast::Type::Owned(o) => format!("owned_{}", mangle_type(&o.ty)),
-        ast::Type::FunctionPointer(fp) => {
-            let mut s = String::from("fn_");
-            if let Some(r) = &fp.returns {
-                s.push_str(&mangle_type(r));
-            } else {
-                s.push_str("void");
-            }
-            s.push_str("_args");
-            for a in &fp.args {
-                s.push('_');
-                s.push_str(&mangle_type(&a.1));
+        ast::Type::Shared(s) => format!("shared_{}", mangle_type(&s.ty)),
+        ast::Type::FunctionPointer(fp) => match fp.fn_ty {
+            ast::FunctionType::Function => format!("fn_{}", mangle_signature(fp)),
+            ast::FunctionType::Closure => format!("closure_{}", mangle_signature(fp)),
+            ast::FunctionType::BorrowedClosure => {
+                format!("borrowed_closure_{}", mangle_signature(fp))
             }
-            s
-        }
+        },
         ast::Type::Array(a) => format!("array_{}_{}", mangle_type(&a.ty), a.len),
+        ast::Type::Optional(o) => format!("optional_{}", mangle_type(&o.ty)),
         ast::Type::Identifier(i) => i.identifier.clone(),
     }
 }
 
+// Mangle the return and argument types of a function pointer.
+fn mangle_signature(fp: &ast::FunctionPointer) -> String {
+    let mut s = String::new();
+    if let Some(r) = &fp.returns {
+        s.push_str(&mangle_type(r));
+    } else {
+        s.push_str("void");
+    }
+    s.push_str("_args");
+    for a in &fp.args {
+        s.push('_');
+        s.push_str(&mangle_type(&a.1));
+    }
+    s
+}
+
 // Print public C types. Key differences vs C++/Python outputs:
-// - Slices/owned use concrete typedef names (vellum_slice_... etc.)
+// - Slices/owned/shared/optional/closures use concrete typedef names
+//   (vellum_slice_... etc.)
 // - Pointers spell const on the pointee type per C conventions.
+// - Named types are referenced through the typedefs emitted in the header.
 struct DisplayTypeC<'a>(&'a ast::Type);
 
 impl std::fmt::Display for DisplayTypeC<'_> {
@@ -309,22 +490,26 @@ impl std::fmt::Display for DisplayTypeC<'_> {
                     ast::Primitive::I16 => "int16_t",
                     ast::Primitive::I32 => "int32_t",
                     ast::Primitive::I64 => "int64_t",
+                    ast::Primitive::I128 => "vellum_int128_t",
                     ast::Primitive::Isize => "ssize_t",
                     ast::Primitive::U8 => "uint8_t",
                     ast::Primitive::U16 => "uint16_t",
                     ast::Primitive::U32 => "uint32_t",
                     ast::Primitive::U64 => "uint64_t",
+                    ast::Primitive::U128 => "vellum_uint128_t",
                     ast::Primitive::Usize => "size_t",
+                    ast::Primitive::CChar => "char",
+                    ast::Primitive::CInt => "int",
+                    ast::Primitive::CLong => "long",
+                    ast::Primitive::CUlong => "unsigned long",
+                    ast::Primitive::F32 => "float",
+                    ast::Primitive::F64 => "double",
                 };
                 write!(f, "{}", s)?;
             }
-            ast::Type::Pointer(p) => {
-                let base = DisplayTypeC(p.ty.as_ref()).to_string();
-                match p.modifier {
-                    ast::PointerModifier::Const => write!(f, "{} const *", base)?,
-                    ast::PointerModifier::Mut => write!(f, "{} *", base)?,
-                }
-            }
+            ast::Type::Void { .. } => write!(f, "void")?,
+            ast::Type::Pointer(p) => write!(f, "{}", pointer_c_type(p, "VELLUM_NONNULL"))?,
+            ast::Type::OptionalPointer(p) => write!(f, "{}", pointer_c_type(p, "VELLUM_NULLABLE"))?,
             ast::Type::String(s) => match s.modifier {
                 ast::PointerModifier::Const => write!(f, "char const *")?,
                 ast::PointerModifier::Mut => write!(f, "char *")?,
@@ -359,53 +544,121 @@ impl std::fmt::Display for DisplayTypeC<'_> {
                     write!(f, "{}", name)?;
                 }
             },
+            ast::Type::Shared(s) => {
+                write!(f, "vellum_shared_ptr_{}", mangle_type(&s.ty))?;
+            }
             ast::Type::FunctionPointer(ast::FunctionPointer {
                 fn_ty,
                 args,
                 returns,
                 ..
-            }) => {
-                match fn_ty {
-                    ast::FunctionType::Function => {
-                        write!(
-                            f,
-                            "{} (*) (",
-                            returns
-                                .as_ref()
-                                .map(|t| DisplayTypeC(t).to_string())
-                                .unwrap_or("void".into())
-                        )?;
-                        for (i, a) in args.iter().enumerate() {
-                            if i > 0 {
-                                write!(f, ", ")?;
-                            }
-                            write!(f, "{}", DisplayTypeC(&a.1))?;
+            }) => match fn_ty {
+                ast::FunctionType::Function => {
+                    write!(
+                        f,
+                        "{} (*) (",
+                        returns
+                            .as_ref()
+                            .map(|t| DisplayTypeC(t).to_string())
+                            .unwrap_or("void".into())
+                    )?;
+                    for (i, a) in args.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
                         }
-                        write!(f, ")")?;
-                    }
-                    ast::FunctionType::Closure => {
-                        // Placeholder; fields would be emitted if closures are present
-                        write!(f, "struct vellum_closure")?;
+                        write!(f, "{}", DisplayTypeC(&a.1))?;
                     }
+                    write!(f, ")")?;
                 }
-            }
+                ast::FunctionType::Closure | ast::FunctionType::BorrowedClosure => {
+                    write!(f, "vellum_{}", mangle_type(self.0))?;
+                }
+            },
             ast::Type::Array(a) => {
                 write!(f, "{}[{}]", DisplayTypeC(&a.ty), a.len)?;
             }
-            ast::Type::Identifier(i) => write!(f, "struct {}", i.identifier)?,
+            ast::Type::Optional(o) => write!(f, "vellum_optional_{}", mangle_type(&o.ty))?,
+            ast::Type::Identifier(i) => write!(f, "{}", i.identifier)?,
         }
         Ok(())
     }
 }
 
+// Pointers are annotated with their nullability, which is checked by Clang.
+fn pointer_c_type(p: &ast::Pointer, nullability: &str) -> String {
+    let base = DisplayTypeC(p.ty.as_ref()).to_string();
+    match p.modifier {
+        ast::PointerModifier::Const => format!("{} const * {}", base, nullability),
+        ast::PointerModifier::Mut => format!("{} * {}", base, nullability),
+    }
+}
+
+// Print a C declaration of `declarator` with type `ty`.
+// Arrays and function pointers are spelled around the declarator.
+fn declare(ty: &ast::Type, declarator: &str) -> String {
+    match ty {
+        ast::Type::Array(a) => declare(&a.ty, &format!("{}[{}]", declarator, a.len)),
+        ast::Type::FunctionPointer(fp) if matches!(fp.fn_ty, ast::FunctionType::Function) => {
+            let args = if fp.args.is_empty() {
+                "void".to_string()
+            } else {
+                fp.args
+                    .iter()
+                    .map(|a| DisplayTypeC(&a.1).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            let declarator = format!("(*{})({})", declarator, args);
+            match &fp.returns {
+                Some(returns) => declare(returns, &declarator),
+                None => format!("void {}", declarator),
+            }
+        }
+        _ => format!("{} {}", DisplayTypeC(ty), declarator),
+    }
+}
+
+// Declaration of the `call` member of a closure typedef, which takes the state first.
+fn closure_call_decl(fp: &ast::FunctionPointer) -> String {
+    let args = std::iter::once("void *".to_string())
+        .chain(fp.args.iter().map(|a| DisplayTypeC(&a.1).to_string()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let declarator = format!("(*call)({})", args);
+    match &fp.returns {
+        Some(returns) => declare(returns, &declarator),
+        None => format!("void {}", declarator),
+    }
+}
+
+// Declaration of the `data` member of a slice typedef.
+fn slice_data_decl(s: &ast::Slice) -> String {
+    let is_const = matches!(s.modifier, ast::PointerModifier::Const);
+    match s.ty.as_ref() {
+        ast::Type::Array(_) => {
+            let decl = declare(&s.ty, "(*data)");
+            if is_const {
+                format!("const {}", decl)
+            } else {
+                decl
+            }
+        }
+        _ => format!("{} * data", c_type(&s.ty, is_const)),
+    }
+}
+
 // Internal helper for typedefs: element type for slices and owned.
 // Returns concrete slice typedef for nested slices.
 fn c_type(ty: &ast::Type, is_const: bool) -> String {
     match ty {
         ast::Type::Primitive { .. }
         | ast::Type::String(_)
+        | ast::Type::Void { .. }
         | ast::Type::Pointer(_)
+        | ast::Type::OptionalPointer(_)
         | ast::Type::Array(_)
+        | ast::Type::Optional(_)
+        | ast::Type::Shared(_)
         | ast::Type::Identifier(_) => {
             let base = DisplayTypeC(ty).to_string();
             if is_const {
@@ -439,6 +692,23 @@ mod filters {
         Ok(DisplayTypeC(ty).to_string())
     }
 
+    pub fn decl(ty: &ast::Type, _: &dyn askama::Values, name: &str) -> askama::Result<String> {
+        Ok(declare(ty, name))
+    }
+
+    // A method pointer, which takes the object implementing the interface first.
+    pub fn method_decl(method: &Method, _: &dyn askama::Values) -> askama::Result<String> {
+        let args = std::iter::once("void *self".to_string())
+            .chain(method.args.iter().map(|arg| declare(&arg.ty, &arg.name)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let declarator = format!("(*{})({})", method.name, args);
+        Ok(match &method.returns {
+            Some(returns) => declare(returns, &declarator),
+            None => format!("void {}", declarator),
+        })
+    }
+
     pub fn retty(ty: &Option<ast::Type>, _: &dyn askama::Values) -> askama::Result<String> {
         if let Some(ty) = ty {
             Ok(DisplayTypeC(ty).to_string())
@@ -446,4 +716,43 @@ mod filters {
             Ok("void".into())
         }
     }
+
+    // Attributes that follow a declaration, with a leading space.
+    fn gnu_attributes(specifiers: Vec<String>) -> String {
+        if specifiers.is_empty() {
+            String::new()
+        } else {
+            format!(" __attribute__(({}))", specifiers.join(", "))
+        }
+    }
+
+    pub fn attributes(attributes: &Attributes, _: &dyn askama::Values) -> askama::Result<String> {
+        let mut specifiers = Vec::new();
+        match attributes.deprecated() {
+            Some(Some(message)) => specifiers.push(format!("deprecated({:?})", message)),
+            Some(None) => specifiers.push("deprecated".to_string()),
+            None => {}
+        }
+        if let Some(align) = attributes.align() {
+            specifiers.push(format!("aligned({})", align));
+        }
+        Ok(gnu_attributes(specifiers))
+    }
+
+    // Struct layout attributes. Deprecation is only noted in the docs, since structs are
+    // referenced throughout the header.
+    pub fn layout(attributes: &Attributes, _: &dyn askama::Values) -> askama::Result<String> {
+        let mut specifiers = Vec::new();
+        if attributes.packed() {
+            specifiers.push("packed".to_string());
+        }
+        if let Some(align) = attributes.align() {
+            specifiers.push(format!("aligned({})", align));
+        }
+        Ok(gnu_attributes(specifiers))
+    }
+
+    pub fn int_literal(value: &i128, _: &dyn askama::Values) -> askama::Result<String> {
+        Ok(super::int_literal(*value, "vellum_uint128_t"))
+    }
 }
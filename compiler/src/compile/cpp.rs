@@ -1,7 +1,7 @@
-use super::{Compile, Items, Mode};
+use super::{Attributes, Compile, Definition, Items, Mode, int_literal, qualify};
 use crate::parse::{Context, ast};
 use askama::Template;
-use codespan_reporting::diagnostic::Diagnostic;
+use codespan_reporting::diagnostic::{Diagnostic, Label};
 use std::{
     fs::OpenOptions,
     io::{Error, Write},
@@ -21,7 +21,108 @@ struct CppExportInlineTemplate {
     header_name: String,
 }
 
-pub(super) fn compile(context: &mut Context, options: Compile, items: Items) -> Result<(), ()> {
+/// Reserved words of C++20.
+const KEYWORDS: &[&str] = &[
+    "alignas",
+    "alignof",
+    "and",
+    "and_eq",
+    "asm",
+    "auto",
+    "bitand",
+    "bitor",
+    "bool",
+    "break",
+    "case",
+    "catch",
+    "char",
+    "char8_t",
+    "char16_t",
+    "char32_t",
+    "class",
+    "compl",
+    "concept",
+    "const",
+    "consteval",
+    "constexpr",
+    "constinit",
+    "const_cast",
+    "continue",
+    "co_await",
+    "co_return",
+    "co_yield",
+    "decltype",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "dynamic_cast",
+    "else",
+    "enum",
+    "explicit",
+    "export",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "friend",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "not_eq",
+    "nullptr",
+    "operator",
+    "or",
+    "or_eq",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "reinterpret_cast",
+    "requires",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "static_cast",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "thread_local",
+    "throw",
+    "true",
+    "try",
+    "typedef",
+    "typeid",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "wchar_t",
+    "while",
+    "xor",
+    "xor_eq",
+];
+
+pub(super) fn compile(context: &mut Context, options: Compile, mut items: Items) -> Result<(), ()> {
+    items.escape_keywords(context, "C++", KEYWORDS, true)?;
+    check_bitfields(context, &items)?;
+    check_interfaces(context, &items)?;
+    check_handles(context, &items)?;
+
     let file_stem = Path::new(&options.file)
         .file_stem()
         .unwrap()
@@ -56,6 +157,95 @@ pub(super) fn compile(context: &mut Context, options: Compile, items: Items) ->
     Ok(())
 }
 
+/// Check that no struct needs an aligned bitfield, which C++ doesn't allow.
+///
+/// The first field of a struct is aligned to follow all of its base, but this is only necessary
+/// when the base is itself derived, since other bases have no tail padding to reuse.
+fn check_bitfields(context: &mut Context, items: &Items) -> Result<(), ()> {
+    let mut valid = true;
+    for d in &items.definitions {
+        if let Definition::Struct(s) = d
+            && let Some(base) = &s.base
+            && items.is_derived(base)
+            && s.fields.first().is_some_and(|f| f.width.is_some())
+        {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!(
+                        "struct `{}` can't start with a bitfield in C++",
+                        qualify(&s.package, &s.name, ".")
+                    ))
+                    .with_labels(vec![Label::primary(
+                        s.location.file_id,
+                        s.location.span.clone(),
+                    )])
+                    .with_notes(vec![
+                        "the first field must be aligned to follow the base, which is itself derived, and bitfields can't be aligned"
+                            .to_string(),
+                    ]),
+            );
+        }
+    }
+    if valid { Ok(()) } else { Err(()) }
+}
+
+/// Check that no interface uses the names of its C++ helpers.
+fn check_interfaces(context: &mut Context, items: &Items) -> Result<(), ()> {
+    let mut valid = true;
+    for d in &items.definitions {
+        if let Definition::Interface(i) = d {
+            let names = std::iter::once((&i.name, &i.location))
+                .chain(i.methods.iter().map(|m| (&m.name, &m.location)));
+            for (name, location) in
+                names.filter(|(name, _)| ["Interface", "from"].contains(&name.as_str()))
+            {
+                valid = false;
+                context.report(
+                    &Diagnostic::error()
+                        .with_message(format!(
+                            "interface `{}` can't use the name `{}` in C++",
+                            qualify(&i.package, &i.name, "."),
+                            name
+                        ))
+                        .with_labels(vec![Label::primary(
+                            location.file_id,
+                            location.span.clone(),
+                        )])
+                        .with_notes(vec![
+                            "the interface has a nested `Interface` base class and a `from` function"
+                                .to_string(),
+                        ]),
+                );
+            }
+        }
+    }
+    if valid { Ok(()) } else { Err(()) }
+}
+
+/// Check that no method uses the name of the pointer held by its handle.
+fn check_handles(context: &mut Context, items: &Items) -> Result<(), ()> {
+    let mut valid = true;
+    for i in items.impls() {
+        for f in i.functions.iter().filter(|f| f.method_name() == "self") {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!(
+                        "handle `{}` can't have a method named `self` in C++",
+                        qualify(i.package, &i.name, ".")
+                    ))
+                    .with_labels(vec![Label::primary(
+                        f.location.file_id,
+                        f.location.span.clone(),
+                    )])
+                    .with_notes(vec!["the handle holds its pointer in `self`".to_string()]),
+            );
+        }
+    }
+    if valid { Ok(()) } else { Err(()) }
+}
+
 fn output_path(output_dir: Option<&PathBuf>, file_name: &str) -> PathBuf {
     if let Some(dir) = output_dir {
         dir.join(file_name)
@@ -76,7 +266,11 @@ fn compile_header(items: Items, output_file: &Path) -> Result<(), Error> {
     Ok(())
 }
 
-fn compile_export_inline(items: Items, header_name: String, output_file: &Path) -> Result<(), Error> {
+fn compile_export_inline(
+    items: Items,
+    header_name: String,
+    output_file: &Path,
+) -> Result<(), Error> {
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
@@ -88,8 +282,26 @@ fn compile_export_inline(items: Items, header_name: String, output_file: &Path)
     Ok(())
 }
 
+// Names in packages are spelled with their full namespace, since they may be
+// referenced from another package's namespace.
+fn cpp_name(name: &str) -> String {
+    let (package, name) = ast::split_qualified(name);
+    if package.is_empty() {
+        name.to_string()
+    } else {
+        format!("::{}", qualify(&package, name, "::"))
+    }
+}
+
+fn array_len(len: &ast::Expr) -> String {
+    match len {
+        ast::Expr::Constant(c) => cpp_name(&c.identifier),
+        len => len.to_string(),
+    }
+}
+
 struct DisplayTypeAbi<'a>(&'a ast::Type);
-struct DisplayTypeRaii<'a>(&'a ast::Type);
+struct DisplayTypeRaii<'a>(&'a ast::Type, &'a Items);
 
 impl std::fmt::Display for DisplayTypeAbi<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -101,16 +313,25 @@ impl std::fmt::Display for DisplayTypeAbi<'_> {
                     ast::Primitive::I16 => "int16_t",
                     ast::Primitive::I32 => "int32_t",
                     ast::Primitive::I64 => "int64_t",
+                    ast::Primitive::I128 => "vellum::int128_t",
                     ast::Primitive::Isize => "ssize_t",
                     ast::Primitive::U8 => "uint8_t",
                     ast::Primitive::U16 => "uint16_t",
                     ast::Primitive::U32 => "uint32_t",
                     ast::Primitive::U64 => "uint64_t",
+                    ast::Primitive::U128 => "vellum::uint128_t",
                     ast::Primitive::Usize => "size_t",
+                    ast::Primitive::CChar => "char",
+                    ast::Primitive::CInt => "int",
+                    ast::Primitive::CLong => "long",
+                    ast::Primitive::CUlong => "unsigned long",
+                    ast::Primitive::F32 => "float",
+                    ast::Primitive::F64 => "double",
                 };
                 write!(f, "{}", s)?;
             }
-            ast::Type::Pointer(p) => {
+            ast::Type::Void { .. } => write!(f, "void")?,
+            ast::Type::Pointer(p) | ast::Type::OptionalPointer(p) => {
                 let modifier = match p.modifier {
                     ast::PointerModifier::Const => " const",
                     ast::PointerModifier::Mut => "",
@@ -139,6 +360,9 @@ impl std::fmt::Display for DisplayTypeAbi<'_> {
             ast::Type::Owned(p) => {
                 write!(f, "vellum::detail::abi::owned<{}>", DisplayTypeAbi(&p.ty))?;
             }
+            ast::Type::Shared(p) => {
+                write!(f, "vellum::detail::abi::shared<{}>", DisplayTypeAbi(&p.ty))?;
+            }
             ast::Type::FunctionPointer(ast::FunctionPointer {
                 fn_ty,
                 args,
@@ -148,6 +372,7 @@ impl std::fmt::Display for DisplayTypeAbi<'_> {
                 let fn_ty_name = match fn_ty {
                     ast::FunctionType::Function => "function",
                     ast::FunctionType::Closure => "detail::abi::closure",
+                    ast::FunctionType::BorrowedClosure => "detail::abi::borrowed_closure",
                 };
                 let fn_ret_ty = if let Some(returns) = &returns {
                     DisplayTypeAbi(returns).to_string()
@@ -165,9 +390,15 @@ impl std::fmt::Display for DisplayTypeAbi<'_> {
                 write!(f, ")>")?;
             }
             ast::Type::Array(a) => {
-                write!(f, "std::array<{}, {}>", DisplayTypeAbi(&a.ty), a.len)?;
+                write!(
+                    f,
+                    "std::array<{}, {}>",
+                    DisplayTypeAbi(&a.ty),
+                    array_len(&a.len)
+                )?;
             }
-            ast::Type::Identifier(i) => write!(f, "{}", i.identifier)?,
+            ast::Type::Optional(o) => write!(f, "vellum::optional<{}>", DisplayTypeAbi(&o.ty))?,
+            ast::Type::Identifier(i) => write!(f, "{}", cpp_name(&i.identifier))?,
         }
         Ok(())
     }
@@ -183,21 +414,35 @@ impl std::fmt::Display for DisplayTypeRaii<'_> {
                     ast::Primitive::I16 => "int16_t",
                     ast::Primitive::I32 => "int32_t",
                     ast::Primitive::I64 => "int64_t",
+                    ast::Primitive::I128 => "vellum::int128_t",
                     ast::Primitive::Isize => "ssize_t",
                     ast::Primitive::U8 => "uint8_t",
                     ast::Primitive::U16 => "uint16_t",
                     ast::Primitive::U32 => "uint32_t",
                     ast::Primitive::U64 => "uint64_t",
+                    ast::Primitive::U128 => "vellum::uint128_t",
                     ast::Primitive::Usize => "size_t",
+                    ast::Primitive::CChar => "char",
+                    ast::Primitive::CInt => "int",
+                    ast::Primitive::CLong => "long",
+                    ast::Primitive::CUlong => "unsigned long",
+                    ast::Primitive::F32 => "float",
+                    ast::Primitive::F64 => "double",
                 };
                 write!(f, "{}", s)?;
             }
-            ast::Type::Pointer(p) => {
+            ast::Type::Void { .. } => write!(f, "void")?,
+            ast::Type::Pointer(p) | ast::Type::OptionalPointer(p) => {
                 let modifier = match p.modifier {
                     ast::PointerModifier::Const => " const",
                     ast::PointerModifier::Mut => "",
                 };
-                write!(f, "{}{} *", DisplayTypeRaii(p.ty.as_ref()), modifier)?;
+                write!(
+                    f,
+                    "{}{} *",
+                    DisplayTypeRaii(p.ty.as_ref(), self.1),
+                    modifier
+                )?;
             }
             ast::Type::String(s) => {
                 let modifier = match s.modifier {
@@ -215,11 +460,14 @@ impl std::fmt::Display for DisplayTypeRaii<'_> {
                     f,
                     "vellum::slice<{}{}>",
                     modifier,
-                    DisplayTypeRaii(s.ty.as_ref())
+                    DisplayTypeRaii(s.ty.as_ref(), self.1)
                 )?;
             }
             ast::Type::Owned(p) => {
-                write!(f, "vellum::owned<{}>", DisplayTypeRaii(&p.ty))?;
+                write!(f, "vellum::owned<{}>", DisplayTypeRaii(&p.ty, self.1))?;
+            }
+            ast::Type::Shared(p) => {
+                write!(f, "vellum::shared<{}>", DisplayTypeRaii(&p.ty, self.1))?;
             }
             ast::Type::FunctionPointer(ast::FunctionPointer {
                 fn_ty,
@@ -230,9 +478,10 @@ impl std::fmt::Display for DisplayTypeRaii<'_> {
                 let fn_ty_name = match fn_ty {
                     ast::FunctionType::Function => "function",
                     ast::FunctionType::Closure => "closure",
+                    ast::FunctionType::BorrowedClosure => "borrowed_closure",
                 };
                 let fn_ret_ty = if let Some(returns) = &returns {
-                    DisplayTypeRaii(returns).to_string()
+                    DisplayTypeRaii(returns, self.1).to_string()
                 } else {
                     "void".to_string()
                 };
@@ -240,16 +489,43 @@ impl std::fmt::Display for DisplayTypeRaii<'_> {
                 write!(f, "vellum::{}<{} (", fn_ty_name, fn_ret_ty,)?;
                 if !args.is_empty() {
                     for arg in args.iter().take(args.len() - 1) {
-                        write!(f, "{}, ", DisplayTypeRaii(&arg.1))?;
+                        write!(f, "{}, ", DisplayTypeRaii(&arg.1, self.1))?;
                     }
-                    write!(f, "{}", DisplayTypeRaii(&args.last().unwrap().1))?;
+                    write!(f, "{}", DisplayTypeRaii(&args.last().unwrap().1, self.1))?;
                 }
                 write!(f, ")>")?;
             }
             ast::Type::Array(a) => {
-                write!(f, "std::array<{}, {}>", DisplayTypeRaii(&a.ty), a.len)?;
+                write!(
+                    f,
+                    "std::array<{}, {}>",
+                    DisplayTypeRaii(&a.ty, self.1),
+                    array_len(&a.len)
+                )?;
+            }
+            ast::Type::Optional(o) => {
+                // Values convert to their RAII wrappers, except for arrays, which can't convert
+                // element by element, so arrays of wrapped types stay in the ABI type
+                let value = DisplayTypeRaii(&o.ty, self.1).to_string();
+                if value != DisplayTypeAbi(&o.ty).to_string() && self.1.is_array(&o.ty) {
+                    write!(f, "{}", DisplayTypeAbi(self.0))?;
+                } else {
+                    write!(f, "std::optional<{}>", value)?;
+                }
+            }
+            ast::Type::Identifier(i) => {
+                // Aliases of types with distinct RAII wrappers are spelled out,
+                // since the alias itself names the ABI type
+                match self.1.get_alias(&i.identifier) {
+                    Some(a)
+                        if DisplayTypeRaii(&a.ty, self.1).to_string()
+                            != DisplayTypeAbi(&a.ty).to_string() =>
+                    {
+                        write!(f, "{}", DisplayTypeRaii(&a.ty, self.1))?
+                    }
+                    _ => write!(f, "{}", cpp_name(&i.identifier))?,
+                }
             }
-            ast::Type::Identifier(i) => write!(f, "{}", i.identifier)?,
         }
         Ok(())
     }
@@ -266,19 +542,72 @@ mod filters {
         if let Some(ty) = ty {
             Ok(DisplayTypeAbi(ty).to_string())
         } else {
-            return Ok("void".to_string());
+            Ok("void".to_string())
+        }
+    }
+
+    pub fn qualified(
+        name: &str,
+        _: &dyn askama::Values,
+        package: &[String],
+    ) -> askama::Result<String> {
+        Ok(qualify(package, name, "::"))
+    }
+
+    // Attributes that precede a declaration, with a trailing space.
+    pub fn attributes(attributes: &Attributes, _: &dyn askama::Values) -> askama::Result<String> {
+        let mut result = match attributes.deprecated() {
+            Some(Some(message)) => format!("[[deprecated({:?})]] ", message),
+            Some(None) => "[[deprecated]] ".to_string(),
+            None => String::new(),
+        };
+        if let Some(align) = attributes.align() {
+            result.push_str(&format!("alignas({}) ", align));
         }
+        Ok(result)
     }
 
-    pub fn ty_raii(ty: &ast::Type, _: &dyn askama::Values) -> askama::Result<String> {
-        Ok(DisplayTypeRaii(ty).to_string())
+    // Struct layout attributes. C++ has no standard way to pack a struct.
+    pub fn layout(attributes: &Attributes, _: &dyn askama::Values) -> askama::Result<String> {
+        let mut result = String::new();
+        if attributes.packed() {
+            result.push_str("[[gnu::packed]] ");
+        }
+        if let Some(align) = attributes.align() {
+            result.push_str(&format!("alignas({}) ", align));
+        }
+        Ok(result)
+    }
+
+    pub fn int_literal(value: &i128, _: &dyn askama::Values) -> askama::Result<String> {
+        Ok(super::int_literal(*value, "vellum::uint128_t"))
+    }
+
+    // Non-null pointers in function signatures are checked when crossing the ABI, and owned
+    // pointers to structs with destructors are held by their handles.
+    pub fn ty_raii(
+        ty: &ast::Type,
+        _: &dyn askama::Values,
+        items: &Items,
+    ) -> askama::Result<String> {
+        if let Some((package, name)) = items.owning_handle(ty) {
+            Ok(cpp_name(&qualify(package, &name, ".")))
+        } else if let ast::Type::Pointer(_) = ty {
+            Ok(format!("vellum::not_null<{}>", DisplayTypeRaii(ty, items)))
+        } else {
+            Ok(DisplayTypeRaii(ty, items).to_string())
+        }
     }
 
-    pub fn retty_raii(ty: &Option<ast::Type>, _: &dyn askama::Values) -> askama::Result<String> {
+    pub fn retty_raii(
+        ty: &Option<ast::Type>,
+        values: &dyn askama::Values,
+        items: &Items,
+    ) -> askama::Result<String> {
         if let Some(ty) = ty {
-            Ok(DisplayTypeRaii(ty).to_string())
+            ty_raii(ty, values, items)
         } else {
-            return Ok("void".to_string());
+            Ok("void".to_string())
         }
     }
 }
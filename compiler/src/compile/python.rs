@@ -1,11 +1,11 @@
-use super::{Compile, Items};
+use super::{Compile, Definition, Items, Struct, Union, qualify};
 use crate::parse::{Context, ast};
 use askama::Template;
-use codespan_reporting::diagnostic::Diagnostic;
+use codespan_reporting::diagnostic::{Diagnostic, Label};
 use std::{
     fs::OpenOptions,
     io::{Error, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 #[derive(Template)]
@@ -14,16 +14,46 @@ struct PyTemplate {
     items: Items,
 }
 
-pub(super) fn compile(context: &mut Context, options: Compile, items: Items) -> Result<(), ()> {
-    let file_name = Path::new(&options.file)
-        .with_extension("py")
-        .file_name()
-        .unwrap()
-        .to_owned();
+/// Reserved words of Python, which can't be used as attribute names.
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+pub(super) fn compile(context: &mut Context, options: Compile, mut items: Items) -> Result<(), ()> {
+    // Items in this file's package are named by the module, anything else is prefixed
+    let package = items.package.clone();
+    items.flatten_names(|p, name| {
+        if p == package {
+            name.to_string()
+        } else {
+            qualify(p, name, "_")
+        }
+    });
+
+    items.escape_keywords(context, "Python", KEYWORDS, true)?;
+    check_bitfields(context, &items)?;
+    check_interfaces(context, &items)?;
+    check_handles(context, &items)?;
+
+    // A package determines the module path
+    let file_name = if package.is_empty() {
+        Path::new(&options.file)
+            .with_extension("py")
+            .file_name()
+            .unwrap()
+            .into()
+    } else {
+        let mut path = package.iter().collect::<PathBuf>();
+        path.set_extension("py");
+        path
+    };
     let output_file = if let Some(output_dir) = options.resolve_output_dir() {
         output_dir.join(file_name)
     } else {
-        file_name.into()
+        file_name
     };
 
     if let Err(e) = compile_impl(items, &output_file) {
@@ -39,9 +69,13 @@ pub(super) fn compile(context: &mut Context, options: Compile, items: Items) ->
 }
 
 fn compile_impl(items: Items, output_file: &Path) -> Result<(), Error> {
+    if let Some(parent) = output_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(output_file)?;
 
     let template = PyTemplate { items };
@@ -49,7 +83,9 @@ fn compile_impl(items: Items, output_file: &Path) -> Result<(), Error> {
     Ok(())
 }
 
-struct DisplayType<'a>(&'a ast::Type);
+// Print ctypes types. Enums are passed as their representation, so printing
+// needs the rest of the items.
+struct DisplayType<'a>(&'a ast::Type, &'a Items);
 
 impl std::fmt::Display for DisplayType<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -61,18 +97,32 @@ impl std::fmt::Display for DisplayType<'_> {
                     ast::Primitive::I16 => "ct.c_int16",
                     ast::Primitive::I32 => "ct.c_int32",
                     ast::Primitive::I64 => "ct.c_int64",
+                    ast::Primitive::I128 => "vellum.int128",
                     ast::Primitive::Isize => "ct.c_ssize_t",
                     ast::Primitive::U8 => "ct.c_uint8",
                     ast::Primitive::U16 => "ct.c_uint16",
                     ast::Primitive::U32 => "ct.c_uint32",
                     ast::Primitive::U64 => "ct.c_uint64",
+                    ast::Primitive::U128 => "vellum.uint128",
                     ast::Primitive::Usize => "ct.c_size_t",
+                    ast::Primitive::CChar => "ct.c_char",
+                    ast::Primitive::CInt => "ct.c_int",
+                    ast::Primitive::CLong => "ct.c_long",
+                    ast::Primitive::CUlong => "ct.c_ulong",
+                    ast::Primitive::F32 => "ct.c_float",
+                    ast::Primitive::F64 => "ct.c_double",
                 };
                 write!(f, "{}", s)?;
             }
-            ast::Type::Pointer(p) => {
-                // ctypes doesn't have constness
-                write!(f, "ct.POINTER({})", DisplayType(p.ty.as_ref()))?;
+            // ctypes spells void as None
+            ast::Type::Void { .. } => write!(f, "None")?,
+            ast::Type::Pointer(p) | ast::Type::OptionalPointer(p) => {
+                // ctypes doesn't have constness or nullability
+                if self.1.is_void(&p.ty) {
+                    write!(f, "ct.c_void_p")?;
+                } else {
+                    write!(f, "ct.POINTER({})", DisplayType(p.ty.as_ref(), self.1))?;
+                }
             }
             ast::Type::String(_) => {
                 // ctypes doesn't have constness
@@ -80,11 +130,14 @@ impl std::fmt::Display for DisplayType<'_> {
             }
             ast::Type::Slice(s) => {
                 // ctypes doesn't have constness
-                write!(f, "vellum.Slice({})", DisplayType(s.ty.as_ref()))?;
+                write!(f, "vellum.Slice({})", DisplayType(s.ty.as_ref(), self.1))?;
             }
             ast::Type::Owned(p) => {
                 // ctypes doesn't have constness
-                write!(f, "vellum.Owned({})", DisplayType(p.ty.as_ref()))?;
+                write!(f, "vellum.Owned({})", DisplayType(p.ty.as_ref(), self.1))?;
+            }
+            ast::Type::Shared(p) => {
+                write!(f, "vellum.Shared({})", DisplayType(p.ty.as_ref(), self.1))?;
             }
             ast::Type::FunctionPointer(ast::FunctionPointer {
                 fn_ty,
@@ -93,57 +146,290 @@ impl std::fmt::Display for DisplayType<'_> {
                 ..
             }) => {
                 let fn_ret_ty = if let Some(returns) = &returns {
-                    DisplayType(returns).to_string()
+                    DisplayType(returns, self.1).to_string()
                 } else {
                     "None".to_string()
                 };
                 let fn_ty_name = match fn_ty {
                     ast::FunctionType::Function => "ct.CFUNCTYPE",
                     ast::FunctionType::Closure => "vellum.Closure",
+                    ast::FunctionType::BorrowedClosure => "vellum.BorrowedClosure",
                 };
 
                 write!(f, "{}({}", fn_ty_name, fn_ret_ty)?;
 
                 for arg in args.iter() {
-                    write!(f, ", {}", DisplayType(&arg.1))?;
+                    write!(f, ", {}", DisplayType(&arg.1, self.1))?;
                 }
 
                 write!(f, ")")?;
             }
             ast::Type::Array(a) => {
-                write!(f, "({} * {})", DisplayType(&a.ty), a.len)?;
+                write!(f, "({} * {})", DisplayType(&a.ty, self.1), a.len)?;
+            }
+            ast::Type::Optional(o) => {
+                write!(f, "vellum.Optional({})", DisplayType(o.ty.as_ref(), self.1))?;
+            }
+            ast::Type::Identifier(i) => {
+                if let Some(e) = self.1.get_enum(&i.identifier) {
+                    write!(f, "{}", DisplayType(&e.repr, self.1))?;
+                } else {
+                    write!(f, "{}", i.identifier)?;
+                }
             }
-            ast::Type::Identifier(i) => write!(f, "{}", i.identifier)?,
         }
         Ok(())
     }
 }
 
+/// Check that no struct with bitfields needs an explicit layout, since the padding between
+/// fields can't be computed for bitfields.
+fn check_bitfields(context: &mut Context, items: &Items) -> Result<(), ()> {
+    let mut valid = true;
+    for d in &items.definitions {
+        if let Definition::Struct(s) = d
+            && s.fields.iter().any(|f| f.width.is_some())
+            && struct_overaligned(s, items)
+        {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!(
+                        "struct `{}` can't contain bitfields in Python",
+                        s.name
+                    ))
+                    .with_labels(vec![Label::primary(
+                        s.location.file_id,
+                        s.location.span.clone(),
+                    )])
+                    .with_notes(vec![
+                        "the struct is laid out explicitly, because it is overaligned or follows a packed base, which ctypes doesn't support for bitfields"
+                            .to_string(),
+                    ]),
+            );
+        }
+    }
+    if valid { Ok(()) } else { Err(()) }
+}
+
+/// Check that no method shadows the helpers of `vellum.Interface`.
+fn check_interfaces(context: &mut Context, items: &Items) -> Result<(), ()> {
+    let mut valid = true;
+    for d in &items.definitions {
+        if let Definition::Interface(i) = d {
+            for method in i
+                .methods
+                .iter()
+                .filter(|m| ["call", "implement"].contains(&m.name.as_str()))
+            {
+                valid = false;
+                context.report(
+                    &Diagnostic::error()
+                        .with_message(format!(
+                            "interface `{}` can't have a method named `{}` in Python",
+                            qualify(&i.package, &i.name, "."),
+                            method.name
+                        ))
+                        .with_labels(vec![Label::primary(
+                            method.location.file_id,
+                            method.location.span.clone(),
+                        )])
+                        .with_notes(vec![
+                            "interfaces have `call` and `implement` methods".to_string(),
+                        ]),
+                );
+            }
+        }
+    }
+    if valid { Ok(()) } else { Err(()) }
+}
+
+/// Check that no method replaces the members a handle uses to hold and release its pointer.
+fn check_handles(context: &mut Context, items: &Items) -> Result<(), ()> {
+    let mut valid = true;
+    for i in items.impls() {
+        let members: &[&str] = if i.destructor.is_some() {
+            &[
+                "__init__",
+                "_lib",
+                "_as_parameter_",
+                "_owned",
+                "close",
+                "__enter__",
+                "__exit__",
+                "__del__",
+            ]
+        } else {
+            &["__init__", "_lib", "_as_parameter_"]
+        };
+        for f in i
+            .functions
+            .iter()
+            .filter(|f| members.contains(&f.method_name()))
+        {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!(
+                        "handle `{}` can't have a method named `{}` in Python",
+                        i.name,
+                        f.method_name()
+                    ))
+                    .with_labels(vec![Label::primary(
+                        f.location.file_id,
+                        f.location.span.clone(),
+                    )])
+                    .with_notes(vec![format!(
+                        "the handle uses {}",
+                        members
+                            .iter()
+                            .map(|m| format!("`{}`", m))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )]),
+            );
+        }
+    }
+    if valid { Ok(()) } else { Err(()) }
+}
+
+/// Returns true if a type contains a struct laid out by `vellum.layout`, whose alignment ctypes
+/// doesn't know.
+///
+/// This includes structs with explicit alignment, which ctypes can't express. Opaque structs and
+/// 128-bit integers are always laid out explicitly, since they are stored as byte arrays.
+fn overaligned(ty: &ast::Type, items: &Items) -> bool {
+    match ty {
+        ast::Type::Primitive {
+            primitive: ast::Primitive::I128 | ast::Primitive::U128,
+            ..
+        } => true,
+        ast::Type::Array(a) => overaligned(&a.ty, items),
+        ast::Type::Optional(o) => overaligned(&o.ty, items),
+        ast::Type::Identifier(i) => items
+            .definitions
+            .iter()
+            .find(|d| d.name() == i.identifier)
+            .is_some_and(|d| match d {
+                Definition::Struct(s) => struct_overaligned(s, items),
+                Definition::Union(u) => union_overaligned(u, items),
+                Definition::Alias(a) => overaligned(&a.ty, items),
+                Definition::Interface(_) => false,
+            }),
+        _ => false,
+    }
+}
+
+fn struct_overaligned(s: &Struct, items: &Items) -> bool {
+    s.layout.is_some()
+        || s.attributes.align().is_some()
+        || s.base.as_ref().is_some_and(|base| {
+            // Subclasses inherit `_pack_`, so fields following a packed base are laid out explicitly
+            overaligned(base, items)
+                || items
+                    .get_struct(base)
+                    .is_some_and(|b| b.attributes.packed())
+        })
+        || s.fields
+            .iter()
+            .any(|f| f.attributes.align().is_some() || overaligned(&f.ty, items))
+}
+
+fn union_overaligned(u: &Union, items: &Items) -> bool {
+    u.variants
+        .iter()
+        .filter_map(|v| v.ty.as_ref())
+        .any(|ty| overaligned(ty, items))
+}
+
 mod filters {
     use super::*;
-    use crate::compile::{Field, Function};
+    use crate::compile::{Argument, Enum, Field, Function, Items, Method, UnionVariant};
 
-    pub fn ty(ty: &ast::Type, _: &dyn askama::Values) -> askama::Result<String> {
-        Ok(DisplayType(ty).to_string())
+    pub fn ty(ty: &ast::Type, _: &dyn askama::Values, items: &Items) -> askama::Result<String> {
+        Ok(DisplayType(ty, items).to_string())
     }
 
-    pub fn retty(ty: &Option<ast::Type>, _: &dyn askama::Values) -> askama::Result<String> {
+    pub fn retty(
+        ty: &Option<ast::Type>,
+        _: &dyn askama::Values,
+        items: &Items,
+    ) -> askama::Result<String> {
         if let Some(ty) = ty {
-            Ok(DisplayType(ty).to_string())
+            Ok(DisplayType(ty, items).to_string())
         } else {
-            return Ok("None".to_string());
+            Ok("None".to_string())
         }
     }
 
+    // The handle returned by a function, if it returns an owned pointer to a struct with a
+    // destructor.
+    pub fn owning_handle(
+        returns: &Option<ast::Type>,
+        _: &dyn askama::Values,
+        items: &Items,
+    ) -> askama::Result<Option<String>> {
+        Ok(returns
+            .as_ref()
+            .and_then(|ty| items.owning_handle(ty))
+            .map(|(_, name)| name))
+    }
+
     pub fn repr(value: &String, _: &dyn askama::Values) -> askama::Result<String> {
         Ok(format!("{:?}", value))
     }
 
+    pub fn optional_repr(value: &Option<&str>, _: &dyn askama::Values) -> askama::Result<String> {
+        Ok(match value {
+            Some(value) => format!("{:?}", value),
+            None => "None".to_string(),
+        })
+    }
+
+    // Indices and names of arguments that must not be null, as a Python list literal.
+    pub fn non_null_args(args: &[Argument], _: &dyn askama::Values) -> askama::Result<String> {
+        let checked = args
+            .iter()
+            .enumerate()
+            .filter(|(_, arg)| matches!(arg.ty, ast::Type::Pointer(_)))
+            .map(|(index, arg)| format!("({}, '{}')", index, arg.name))
+            .collect::<Vec<_>>();
+        Ok(if checked.is_empty() {
+            String::new()
+        } else {
+            format!("[{}]", checked.join(", "))
+        })
+    }
+
+    // Structs and unions containing explicitly aligned structs are laid out by `vellum.layout`.
+    pub fn struct_uses_layout(
+        s: &Struct,
+        _: &dyn askama::Values,
+        items: &Items,
+    ) -> askama::Result<bool> {
+        Ok(struct_overaligned(s, items))
+    }
+
+    pub fn union_uses_layout(
+        u: &Union,
+        _: &dyn askama::Values,
+        items: &Items,
+    ) -> askama::Result<bool> {
+        Ok(union_overaligned(u, items))
+    }
+
+    pub fn optional_int(value: Option<u64>, _: &dyn askama::Values) -> askama::Result<String> {
+        Ok(match value {
+            Some(value) => value.to_string(),
+            None => "None".to_string(),
+        })
+    }
+
     pub fn with_incomplete_note(
-        docs: &Vec<String>,
+        docs: &[String],
         _: &dyn askama::Values,
     ) -> askama::Result<Vec<String>> {
-        let mut lines = docs.clone();
+        let mut lines = docs.to_vec();
         if !lines.is_empty() {
             lines.push(String::new());
         }
@@ -151,31 +437,72 @@ mod filters {
         Ok(lines)
     }
 
-    pub fn field_docs(
-        fields: &Vec<Field>,
-        _: &dyn askama::Values,
-    ) -> askama::Result<Vec<String>> {
+    // Render a "heading" followed by a bullet for each documented member.
+    fn member_docs<'a>(
+        heading: &str,
+        members: impl Iterator<Item = (&'a String, Vec<String>)>,
+    ) -> Vec<String> {
         let mut lines = Vec::new();
         let mut has_any = false;
 
-        for field in fields {
-            if field.docs.is_empty() {
+        for (name, docs) in members {
+            if docs.is_empty() {
                 continue;
             }
 
             if !has_any {
-                lines.push("Fields:".to_string());
+                lines.push(heading.to_string());
                 has_any = true;
             }
 
-            let mut docs = field.docs.iter();
+            let mut docs = docs.iter();
             if let Some(first) = docs.next() {
-                lines.push(format!("- {}: {}", field.name, first));
+                lines.push(format!("- {}: {}", name, first));
             }
             lines.extend(docs.map(|doc| format!("  {}", doc)));
         }
 
-        Ok(lines)
+        lines
+    }
+
+    pub fn field_docs(fields: &[Field], _: &dyn askama::Values) -> askama::Result<Vec<String>> {
+        Ok(member_docs(
+            "Fields:",
+            fields.iter().map(|f| {
+                // Fields can't warn when used, so note deprecation in the docs
+                let mut docs = f.docs.clone();
+                match f.attributes.deprecated() {
+                    Some(Some(message)) => docs.push(format!(" Deprecated: {}", message)),
+                    Some(None) => docs.push(" Deprecated.".to_string()),
+                    None => {}
+                }
+                (&f.name, docs)
+            }),
+        ))
+    }
+
+    pub fn variant_docs(e: &Enum, _: &dyn askama::Values) -> askama::Result<Vec<String>> {
+        Ok(member_docs(
+            if e.flags { "Flags:" } else { "Variants:" },
+            e.variants.iter().map(|v| (&v.name, v.docs.clone())),
+        ))
+    }
+
+    pub fn union_variant_docs(
+        variants: &[UnionVariant],
+        _: &dyn askama::Values,
+    ) -> askama::Result<Vec<String>> {
+        Ok(member_docs(
+            "Variants:",
+            variants.iter().map(|v| (&v.name, v.docs.clone())),
+        ))
+    }
+
+    pub fn method_docs(methods: &[Method], _: &dyn askama::Values) -> askama::Result<Vec<String>> {
+        Ok(member_docs(
+            "Methods:",
+            methods.iter().map(|m| (&m.name, m.docs.clone())),
+        ))
     }
 
     pub fn function_doc_lines(
@@ -16,8 +16,6 @@ pub mod ast;
 
 mod lexer;
 
-pub use lexer::Token;
-
 lalrpop_mod!(
     #[allow(clippy::all)]
     grammar,
@@ -77,7 +75,7 @@ impl Context {
     ) -> Result<ast::File, ()> {
         let (path, file_id, source) = self.add_source(path, location)?;
         let lexer = lexer::Lexer::new(source);
-        let items = grammar::ProgramParser::new()
+        let (docs, package, items) = grammar::ProgramParser::new()
             .parse(file_id, lexer)
             .map_err(|e| match e {
                 ParseError::InvalidToken { location } => {
@@ -117,9 +115,29 @@ impl Context {
                             .with_labels(vec![Label::primary(file_id, left..right)]),
                     );
                 }
-                e => panic!("unexpected lexer error: {:?}", e),
+                ParseError::User { error } => {
+                    let message = match error.kind {
+                        lexer::ErrorKind::InvalidToken => "could not parse".to_string(),
+                        lexer::ErrorKind::IntegerTooLarge => {
+                            "integer literal is too large".to_string()
+                        }
+                        lexer::ErrorKind::Expected(name) => {
+                            format!("expected `{}`", name)
+                        }
+                    };
+                    self.report(
+                        &Diagnostic::error()
+                            .with_message(message)
+                            .with_labels(vec![Label::primary(file_id, error.span)]),
+                    );
+                }
             })?;
-        Ok(ast::File { path, items })
+        Ok(ast::File {
+            path,
+            docs,
+            package,
+            items,
+        })
     }
 }
 
@@ -155,13 +173,13 @@ pub fn parse_program(file: impl AsRef<Path>) -> Result<(Context, ast::File), ()>
         for item in file.items.iter_mut() {
             let current_path = path;
             if let ast::Item {
-                docs: _,
                 item:
                     ast::ItemType::Import(ast::Import {
                         location,
                         path,
                         resolved,
                     }),
+                ..
             } = item
             {
                 assert!(resolved.is_none());
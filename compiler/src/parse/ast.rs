@@ -1,6 +1,12 @@
 pub use super::lexer::Primitive;
 use std::{ops::Range, path::PathBuf};
 
+mod attribute;
+pub use attribute::*;
+
+mod expr;
+pub use expr::*;
+
 mod pointer;
 pub use pointer::*;
 
@@ -25,9 +31,23 @@ pub struct Identifier {
     pub identifier: String,
 }
 
+/// Split a qualified name such as `acme.storage.Config` into its package path and local name.
+pub fn split_qualified(name: &str) -> (Vec<String>, &str) {
+    match name.rsplit_once('.') {
+        Some((package, name)) => (package.split('.').map(String::from).collect(), name),
+        None => (Vec::new(), name),
+    }
+}
+
+/// The C name of a qualified name, with the package path used as a prefix.
+pub fn c_name(name: &str) -> String {
+    name.replace('.', "_")
+}
+
 #[derive(Clone, Debug)]
 pub struct Item {
     pub docs: Vec<String>,
+    pub attributes: Vec<Attribute>,
     pub item: ItemType,
 }
 
@@ -36,17 +56,57 @@ impl Item {
         match &self.item {
             ItemType::Import(i) => &i.location,
             ItemType::Struct(s) => &s.location,
+            ItemType::Enum(e) => &e.location,
+            ItemType::Flags(f) => &f.location,
+            ItemType::Union(u) => &u.location,
+            ItemType::Interface(i) => &i.location,
+            ItemType::Constant(c) => &c.location,
+            ItemType::Alias(a) => &a.location,
             ItemType::Function(f) => &f.location,
         }
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct Argument {
+    pub docs: Vec<String>,
+    pub attributes: Vec<Attribute>,
+    pub name: Identifier,
+    pub ty: Type,
+}
+
 #[derive(Clone, Debug)]
 pub struct Function {
     pub location: Location,
     pub name: Identifier,
-    pub args: Vec<(Identifier, Type)>,
+    pub args: Vec<Argument>,
     pub returns: Option<Box<Type>>,
+    /// The `impl` block containing this function, if any
+    pub method_of: Option<MethodOf>,
+}
+
+/// A function declared in an `impl` block, which is named by its type.
+#[derive(Clone, Debug)]
+pub struct MethodOf {
+    /// The type of the `impl` block
+    pub ty: Type,
+    /// The name of the function within the block
+    pub name: Identifier,
+}
+
+/// A function implemented by each object passed as an interface.
+#[derive(Clone, Debug)]
+pub struct Method {
+    pub docs: Vec<String>,
+    pub function: Function,
+}
+
+/// A set of methods, passed as a pointer to each method along with the object implementing them.
+#[derive(Clone, Debug)]
+pub struct Interface {
+    pub location: Location,
+    pub name: Identifier,
+    pub methods: Vec<Method>,
 }
 
 #[derive(Clone, Debug)]
@@ -60,11 +120,21 @@ pub struct Import {
 pub enum ItemType {
     Import(Import),
     Struct(Struct),
+    Enum(Enum),
+    Flags(Flags),
+    Union(Union),
+    Interface(Interface),
+    Constant(Constant),
+    Alias(Alias),
     Function(Function),
 }
 
 #[derive(Clone, Debug)]
 pub struct File {
     pub path: PathBuf,
+    /// Documentation for the file itself, from `//!` comments.
+    pub docs: Vec<String>,
+    /// The package containing this file's items, as a dotted path such as `acme.storage`.
+    pub package: Option<Identifier>,
     pub items: Vec<Item>,
 }
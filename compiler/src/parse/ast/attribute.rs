@@ -0,0 +1,55 @@
+use super::{Identifier, Location};
+
+#[derive(Clone, Debug)]
+pub enum Literal {
+    String(String),
+    Integer(u64),
+}
+
+#[derive(Clone, Debug)]
+pub enum AttributeArgs {
+    /// `#[name]`
+    None,
+    /// `#[name(a, b)]`
+    List(Vec<Literal>),
+    /// `#[name = a]`
+    Value(Literal),
+}
+
+#[derive(Clone, Debug)]
+pub struct Attribute {
+    pub location: Location,
+    pub name: Identifier,
+    pub args: AttributeArgs,
+}
+
+impl Attribute {
+    /// The string argument, such as `"x"` in `#[name("x")]` or `#[name = "x"]`.
+    pub fn string(&self) -> Option<&str> {
+        match &self.args {
+            AttributeArgs::List(list) => match list.as_slice() {
+                [Literal::String(s)] => Some(s),
+                _ => None,
+            },
+            AttributeArgs::Value(Literal::String(s)) => Some(s),
+            AttributeArgs::Value(_) | AttributeArgs::None => None,
+        }
+    }
+
+    /// The integer argument, such as `8` in `#[name(8)]` or `#[name = 8]`.
+    pub fn integer(&self) -> Option<u64> {
+        match &self.args {
+            AttributeArgs::List(list) => match list.as_slice() {
+                [Literal::Integer(i)] => Some(*i),
+                _ => None,
+            },
+            AttributeArgs::Value(Literal::Integer(i)) => Some(*i),
+            AttributeArgs::Value(_) | AttributeArgs::None => None,
+        }
+    }
+}
+
+/// Find an attribute by name.
+pub fn find_attribute<'a>(attributes: &'a [Attribute], name: &str) -> Option<&'a Attribute> {
+    attributes.iter().find(|a| a.name.identifier == name)
+}
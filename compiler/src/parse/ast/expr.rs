@@ -0,0 +1,84 @@
+use super::{Identifier, Location};
+
+#[derive(Copy, Clone, Debug)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Shl,
+    Or,
+}
+
+impl std::fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let s = match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Shl => "<<",
+            Self::Or => "|",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// An integer constant expression.
+///
+/// The type checker folds expressions into integers, except for references to named constants,
+/// which are kept so that generated code can refer to them by name.
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Integer {
+        location: Location,
+        value: i128,
+    },
+    Constant(Identifier),
+    Binary {
+        location: Location,
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+}
+
+impl Expr {
+    pub fn location(&self) -> &Location {
+        match self {
+            Self::Integer { location, .. } => location,
+            Self::Constant(c) => &c.location,
+            Self::Binary { location, .. } => location,
+        }
+    }
+
+    /// The value of a folded integer expression.
+    pub fn value(&self) -> Option<i128> {
+        match self {
+            Self::Integer { value, .. } => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Collect every constant referenced by this expression.
+    pub fn identifiers_mut<'a>(&'a mut self, identifiers: &mut Vec<&'a mut Identifier>) {
+        match self {
+            Self::Integer { .. } => {}
+            Self::Constant(c) => identifiers.push(c),
+            Self::Binary { lhs, rhs, .. } => {
+                lhs.identifiers_mut(identifiers);
+                rhs.identifiers_mut(identifiers);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Integer { value, .. } => write!(f, "{}", value),
+            Self::Constant(c) => write!(f, "{}", c.identifier),
+            Self::Binary { op, lhs, rhs, .. } => write!(f, "({} {} {})", lhs, op, rhs),
+        }
+    }
+}
@@ -31,3 +31,10 @@ pub struct Owned {
     pub location: Location,
     pub ty: Box<Type>,
 }
+
+/// A reference-counted pointer, which is retained when copied and released when dropped
+#[derive(Clone, Debug)]
+pub struct Shared {
+    pub location: Location,
+    pub ty: Box<Type>,
+}
@@ -1,25 +1,111 @@
-use super::{Identifier, Location, Owned, Pointer, Slice, StringPointer};
+use super::{Attribute, Expr, Identifier, Location, Owned, Pointer, Shared, Slice, StringPointer};
 
 pub use super::Primitive;
 
 #[derive(Clone, Debug)]
 pub struct Field {
     pub docs: Vec<String>,
+    pub attributes: Vec<Attribute>,
     pub name: Identifier,
     pub ty: Type,
+    /// The width in bits, if the field is a bitfield
+    pub width: Option<Expr>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Struct {
     pub location: Location,
     pub name: Identifier,
+    /// The struct whose fields are placed first, which is always a `Type::Identifier`
+    pub base: Option<Type>,
     pub fields: Option<Vec<Field>>,
+    pub layout: Option<OpaqueLayout>,
+}
+
+/// The size and alignment of an abstract struct, whose fields are otherwise private.
+#[derive(Clone, Debug)]
+pub struct OpaqueLayout {
+    pub location: Location,
+    pub size: Expr,
+    pub align: Expr,
+}
+
+#[derive(Clone, Debug)]
+pub struct UnionVariant {
+    pub docs: Vec<String>,
+    pub name: Identifier,
+    pub ty: Option<Type>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Union {
+    pub location: Location,
+    pub name: Identifier,
+    pub variants: Vec<UnionVariant>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Variant {
+    pub docs: Vec<String>,
+    pub location: Location,
+    pub name: Identifier,
+    pub value: Option<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Enum {
+    pub location: Location,
+    pub name: Identifier,
+    pub repr: Type,
+    pub variants: Vec<Variant>,
+}
+
+impl Enum {
+    /// The discriminant of each variant.
+    ///
+    /// Variants without an explicit value take the value of the previous variant plus one, or zero
+    /// for the first variant. Explicit values must already be folded by the type checker.
+    pub fn discriminants(&self) -> Vec<i128> {
+        let mut next = 0;
+        self.variants
+            .iter()
+            .map(|variant| {
+                let value = variant
+                    .value
+                    .as_ref()
+                    .map(|value| value.value().expect("discriminants are folded"))
+                    .unwrap_or(next);
+                next = value + 1;
+                value
+            })
+            .collect()
+    }
+}
+
+/// A named bit, or combination of bits, in a set of flags.
+#[derive(Clone, Debug)]
+pub struct Flag {
+    pub docs: Vec<String>,
+    pub location: Location,
+    pub name: Identifier,
+    pub value: Expr,
+}
+
+/// A set of named bits stored in an integer.
+#[derive(Clone, Debug)]
+pub struct Flags {
+    pub location: Location,
+    pub name: Identifier,
+    pub repr: Type,
+    pub flags: Vec<Flag>,
 }
 
 #[derive(Copy, Clone, Debug)]
 pub enum FunctionType {
     Function,
     Closure,
+    /// A closure without a deleter, which is only valid for the duration of a call
+    BorrowedClosure,
 }
 
 #[derive(Clone, Debug)]
@@ -30,11 +116,33 @@ pub struct FunctionPointer {
     pub returns: Option<Box<Type>>,
 }
 
+#[derive(Clone, Debug)]
+pub struct Alias {
+    pub location: Location,
+    pub name: Identifier,
+    pub ty: Type,
+}
+
+#[derive(Clone, Debug)]
+pub struct Constant {
+    pub location: Location,
+    pub name: Identifier,
+    pub ty: Type,
+    pub value: Expr,
+}
+
 #[derive(Clone, Debug)]
 pub struct Array {
     pub location: Location,
     pub ty: Box<Type>,
-    pub len: u64,
+    pub len: Expr,
+}
+
+/// A value that may be absent, laid out as a `bool` followed by the value.
+#[derive(Clone, Debug)]
+pub struct Optional {
+    pub location: Location,
+    pub ty: Box<Type>,
 }
 
 #[derive(Clone, Debug)]
@@ -43,19 +151,52 @@ pub enum Type {
         location: Location,
         primitive: Primitive,
     },
+    /// The type with no values, which may only be used behind a pointer
+    Void {
+        location: Location,
+    },
     Pointer(Pointer),
+    /// A pointer that may be null
+    OptionalPointer(Pointer),
     String(StringPointer),
     Slice(Slice),
     Owned(Owned),
+    Shared(Shared),
     FunctionPointer(FunctionPointer),
     Array(Array),
+    Optional(Optional),
     Identifier(Identifier),
 }
 
 impl Type {
     /// Iterate over this type and all contained types of this type.
     pub fn iter_tree<'a>(&'a self) -> TypeIterator<'a> {
-        return TypeIterator { stack: vec![self] };
+        TypeIterator { stack: vec![self] }
+    }
+
+    /// Collect every name referenced by this type, including array length constants.
+    pub fn identifiers_mut<'a>(&'a mut self, identifiers: &mut Vec<&'a mut Identifier>) {
+        match self {
+            Self::Primitive { .. } | Self::Void { .. } | Self::String(_) => {}
+            Self::Pointer(p) | Self::OptionalPointer(p) => p.ty.identifiers_mut(identifiers),
+            Self::Slice(s) => s.ty.identifiers_mut(identifiers),
+            Self::Owned(o) => o.ty.identifiers_mut(identifiers),
+            Self::Shared(s) => s.ty.identifiers_mut(identifiers),
+            Self::FunctionPointer(f) => {
+                for (_, ty) in f.args.iter_mut() {
+                    ty.identifiers_mut(identifiers);
+                }
+                if let Some(ty) = f.returns.as_mut() {
+                    ty.identifiers_mut(identifiers);
+                }
+            }
+            Self::Array(a) => {
+                a.ty.identifiers_mut(identifiers);
+                a.len.identifiers_mut(identifiers);
+            }
+            Self::Optional(o) => o.ty.identifiers_mut(identifiers),
+            Self::Identifier(i) => identifiers.push(i),
+        }
     }
 
     pub fn location(&self) -> &Location {
@@ -63,13 +204,16 @@ impl Type {
             Self::Primitive {
                 location,
                 primitive: _,
-            } => &location,
-            Self::Pointer(p) => &p.location,
+            } => location,
+            Self::Void { location } => location,
+            Self::Pointer(p) | Self::OptionalPointer(p) => &p.location,
             Self::String(s) => &s.location,
             Self::Slice(s) => &s.location,
             Self::Owned(o) => &o.location,
+            Self::Shared(s) => &s.location,
             Self::FunctionPointer(f) => &f.location,
             Self::Array(a) => &a.location,
+            Self::Optional(o) => &o.location,
             Self::Identifier(i) => &i.location,
         }
     }
@@ -89,19 +233,22 @@ impl<'a> Iterator for TypeIterator<'a> {
                     location: _,
                     primitive: _,
                 } => {}
-                Type::Pointer(p) => self.stack.push(p.ty.as_ref()),
+                Type::Void { location: _ } => {}
+                Type::Pointer(p) | Type::OptionalPointer(p) => self.stack.push(p.ty.as_ref()),
                 Type::String(_) => {}
                 Type::Slice(s) => self.stack.push(s.ty.as_ref()),
                 Type::Owned(o) => self.stack.push(o.ty.as_ref()),
+                Type::Shared(s) => self.stack.push(s.ty.as_ref()),
                 Type::FunctionPointer(f) => {
                     for (_, ty) in f.args.iter() {
-                        self.stack.push(&ty);
+                        self.stack.push(ty);
                     }
                     if let Some(returns) = &f.returns {
-                        self.stack.push(&returns);
+                        self.stack.push(returns);
                     }
                 }
                 Type::Array(a) => self.stack.push(a.ty.as_ref()),
+                Type::Optional(o) => self.stack.push(o.ty.as_ref()),
                 Type::Identifier(_) => {}
             }
             Some(ty)
@@ -1,4 +1,5 @@
 use logos::Logos;
+use std::{num::IntErrorKind, ops::Range};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Primitive {
@@ -7,16 +8,50 @@ pub enum Primitive {
     I16,
     I32,
     I64,
+    I128,
     Isize,
     U8,
     U16,
     U32,
     U64,
+    U128,
     Usize,
+    CChar,
+    CInt,
+    CLong,
+    CUlong,
+    F32,
+    F64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ErrorKind {
+    #[default]
+    InvalidToken,
+    IntegerTooLarge,
+    /// A name other than the contextual keyword or property expected
+    Expected(&'static str),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub span: Range<usize>,
+}
+
+/// Parse an integer literal, ignoring `_` separators.
+fn parse_integer(digits: &str, radix: u32) -> Result<u64, ErrorKind> {
+    u64::from_str_radix(&digits.replace('_', ""), radix).map_err(|e| match e.kind() {
+        IntErrorKind::PosOverflow => ErrorKind::IntegerTooLarge,
+        _ => ErrorKind::InvalidToken,
+    })
 }
 
 #[derive(Logos, Clone, Debug, PartialEq)]
+#[logos(error = ErrorKind)]
 #[logos(skip r"[ \n\t\f]")]
+#[logos(skip r"//[^\n\r]*")]
+#[logos(skip r"/\*[^*]*\*+([^/*][^*]*\*+)*/")]
 pub enum Token {
     #[token("{")]
     LeftBracket,
@@ -45,9 +80,42 @@ pub enum Token {
     #[token(";")]
     Semicolon,
 
+    #[token("=")]
+    Equals,
+
+    #[token(".")]
+    Dot,
+
+    #[token("#")]
+    Hash,
+
     #[token("*")]
     Asterisk,
 
+    #[token("+")]
+    Plus,
+
+    #[token("-")]
+    Minus,
+
+    #[token("/")]
+    Slash,
+
+    #[token("<<")]
+    ShiftLeft,
+
+    #[token("<")]
+    LessThan,
+
+    #[token(">")]
+    GreaterThan,
+
+    #[token("|")]
+    Pipe,
+
+    #[token("?")]
+    QuestionMark,
+
     #[token("void")]
     Void,
 
@@ -56,23 +124,52 @@ pub enum Token {
     #[token("u16", |_| Primitive::U16)]
     #[token("u32", |_| Primitive::U32)]
     #[token("u64", |_| Primitive::U64)]
+    #[token("u128", |_| Primitive::U128)]
     #[token("usize", |_| Primitive::Usize)]
     #[token("i8", |_| Primitive::I8)]
     #[token("i16", |_| Primitive::I16)]
     #[token("i32", |_| Primitive::I32)]
     #[token("i64", |_| Primitive::I64)]
+    #[token("i128", |_| Primitive::I128)]
     #[token("isize", |_| Primitive::Isize)]
+    #[token("c_char", |_| Primitive::CChar)]
+    #[token("c_int", |_| Primitive::CInt)]
+    #[token("c_long", |_| Primitive::CLong)]
+    #[token("c_ulong", |_| Primitive::CUlong)]
+    #[token("f32", |_| Primitive::F32)]
+    #[token("f64", |_| Primitive::F64)]
     Primitive(Primitive),
 
     #[token("struct")]
     Struct,
 
+    #[token("enum")]
+    Enum,
+
+    #[token("union")]
+    Union,
+
+    #[token("interface")]
+    Interface,
+
+    #[token("impl")]
+    Impl,
+
+    #[token("type")]
+    Type,
+
     #[token("const")]
     Const,
 
     #[token("owned")]
     Owned,
 
+    #[token("shared")]
+    Shared,
+
+    #[token("optional")]
+    Optional,
+
     #[token("mut")]
     Mut,
 
@@ -82,12 +179,18 @@ pub enum Token {
     #[token("import")]
     Import,
 
+    #[token("package")]
+    Package,
+
     #[token("function")]
     Function,
 
     #[token("closure")]
     Closure,
 
+    #[token("borrowed")]
+    Borrowed,
+
     #[token("->")]
     Arrow,
 
@@ -95,19 +198,26 @@ pub enum Token {
         let len = lex.slice().len();
         lex.slice()[1..len-1].to_string()
     })]
+    #[regex(r#""[^\n\r"]*""#, |lex| {
+        let len = lex.slice().len();
+        lex.slice()[1..len-1].to_string()
+    })]
     StringLiteral(String),
 
-    #[regex(r"[0-9]+", |lex| lex.slice().parse().map_err(std::mem::drop))]
+    #[regex(r"[0-9][0-9_]*", |lex| parse_integer(lex.slice(), 10))]
+    #[regex(r"0x[0-9a-fA-F_]+", |lex| parse_integer(&lex.slice()[2..], 16))]
+    #[regex(r"0o[0-7_]+", |lex| parse_integer(&lex.slice()[2..], 8))]
+    #[regex(r"0b[01_]+", |lex| parse_integer(&lex.slice()[2..], 2))]
     IntegerLiteral(u64),
 
-    #[regex(r"[a-zA-Z][a-zA-Z_]*", |lex| lex.slice().to_string())]
+    #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_string())]
     Identifier(String),
 
-    #[regex(r"//[^\n\r]*[\n\r]*", |lex| lex.slice().to_string())]
-    Comment(String),
-
     #[regex(r"///[^\n\r]*[\n\r]*", |lex| lex.slice().to_string())]
     DocComment(String),
+
+    #[regex(r"//![^\n\r]*[\n\r]*", |lex| lex.slice().to_string())]
+    InnerDocComment(String),
 }
 
 pub struct Lexer<'input> {
@@ -123,11 +233,15 @@ impl<'input> Lexer<'input> {
 }
 
 impl<'input> Iterator for Lexer<'input> {
-    type Item = Result<(usize, Token, usize), ()>;
+    type Item = Result<(usize, Token, usize), Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.lexer
-            .next()
-            .map(|token| Ok((self.lexer.span().start, token?, self.lexer.span().end)))
+        self.lexer.next().map(|token| {
+            let span = self.lexer.span();
+            match token {
+                Ok(token) => Ok((span.start, token, span.end)),
+                Err(kind) => Err(Error { kind, span }),
+            }
+        })
     }
 }
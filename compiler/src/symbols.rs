@@ -27,10 +27,17 @@ pub fn symbols(symbols: Symbols) -> Result<(), ()> {
     Ok(())
 }
 
+fn symbol(item: &ast::Item, f: &ast::Function) -> String {
+    match ast::find_attribute(&item.attributes, "link_name").and_then(|a| a.string()) {
+        Some(name) => name.to_string(),
+        None => ast::c_name(&f.name.identifier),
+    }
+}
+
 fn list(items: &[ast::Item]) {
     for item in items {
         if let ast::ItemType::Function(f) = &item.item {
-            println!("{}", f.name.identifier);
+            println!("{}", symbol(item, f));
         }
     }
 }
@@ -40,7 +47,7 @@ fn gnu(items: &[ast::Item]) {
     println!("  global:");
     for item in items {
         if let ast::ItemType::Function(f) = &item.item {
-            println!("    {};", f.name.identifier);
+            println!("    {};", symbol(item, f));
         }
     }
     println!("  local: *;");
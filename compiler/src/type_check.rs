@@ -1,28 +1,121 @@
 use crate::parse::{Context, ast};
 use codespan_reporting::diagnostic::{Diagnostic, Label};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+mod attributes;
+mod constants;
 mod sort_items;
 mod valid;
 
 fn name_of_item(item: &ast::Item) -> ast::Identifier {
     match &item.item {
         ast::ItemType::Struct(s) => s.name.clone(),
+        ast::ItemType::Enum(e) => e.name.clone(),
+        ast::ItemType::Flags(f) => f.name.clone(),
+        ast::ItemType::Union(u) => u.name.clone(),
+        ast::ItemType::Interface(i) => i.name.clone(),
+        ast::ItemType::Constant(c) => c.name.clone(),
+        ast::ItemType::Alias(a) => a.name.clone(),
         ast::ItemType::Function(f) => f.name.clone(),
         ast::ItemType::Import(_) => unimplemented!(),
     }
 }
 
+fn name_of_item_mut(item: &mut ast::Item) -> &mut ast::Identifier {
+    match &mut item.item {
+        ast::ItemType::Struct(s) => &mut s.name,
+        ast::ItemType::Enum(e) => &mut e.name,
+        ast::ItemType::Flags(f) => &mut f.name,
+        ast::ItemType::Union(u) => &mut u.name,
+        ast::ItemType::Interface(i) => &mut i.name,
+        ast::ItemType::Constant(c) => &mut c.name,
+        ast::ItemType::Alias(a) => &mut a.name,
+        ast::ItemType::Function(f) => &mut f.name,
+        ast::ItemType::Import(_) => unimplemented!(),
+    }
+}
+
+fn types_of_item_mut(item: &mut ast::Item) -> Vec<&mut ast::Type> {
+    match &mut item.item {
+        ast::ItemType::Struct(s) => s
+            .base
+            .iter_mut()
+            .chain(s.fields.iter_mut().flatten().map(|field| &mut field.ty))
+            .collect(),
+        ast::ItemType::Enum(e) => vec![&mut e.repr],
+        ast::ItemType::Flags(f) => vec![&mut f.repr],
+        ast::ItemType::Union(u) => u
+            .variants
+            .iter_mut()
+            .filter_map(|variant| variant.ty.as_mut())
+            .collect(),
+        ast::ItemType::Interface(i) => i
+            .methods
+            .iter_mut()
+            .flat_map(|method| {
+                let f = &mut method.function;
+                f.args
+                    .iter_mut()
+                    .map(|arg| &mut arg.ty)
+                    .chain(f.returns.as_deref_mut())
+            })
+            .collect(),
+        ast::ItemType::Constant(c) => vec![&mut c.ty],
+        ast::ItemType::Alias(a) => vec![&mut a.ty],
+        ast::ItemType::Function(f) => f
+            .method_of
+            .iter_mut()
+            .map(|method_of| &mut method_of.ty)
+            .chain(f.args.iter_mut().map(|arg| &mut arg.ty))
+            .chain(f.returns.as_deref_mut())
+            .collect(),
+        ast::ItemType::Import(_) => unimplemented!(),
+    }
+}
+
+fn exprs_of_item_mut(item: &mut ast::Item) -> Vec<&mut ast::Expr> {
+    match &mut item.item {
+        ast::ItemType::Constant(c) => vec![&mut c.value],
+        ast::ItemType::Enum(e) => e
+            .variants
+            .iter_mut()
+            .filter_map(|variant| variant.value.as_mut())
+            .collect(),
+        ast::ItemType::Flags(f) => f.flags.iter_mut().map(|flag| &mut flag.value).collect(),
+        ast::ItemType::Struct(s) => s
+            .layout
+            .iter_mut()
+            .flat_map(|layout| [&mut layout.size, &mut layout.align])
+            .chain(
+                s.fields
+                    .iter_mut()
+                    .flatten()
+                    .filter_map(|f| f.width.as_mut()),
+            )
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Flatten all files into a single map, keyed by the qualified name of each item.
+///
+/// Items in a package are named by their package path, e.g. `acme.storage.Config`, and names
+/// used within a package refer to that package's items before any others.
 fn flatten(context: &mut Context, file: ast::File) -> Result<HashMap<String, ast::Item>, ()> {
     fn flatten(
         context: &mut Context,
         file: ast::File,
         items: &mut HashMap<String, ast::Item>,
     ) -> Result<(), ()> {
-        for item in file.items {
+        let package = file.package.map(|p| p.identifier);
+        for mut item in file.items {
             if let ast::ItemType::Import(i) = item.item {
                 flatten(context, i.resolved.unwrap(), items)?;
             } else {
+                if let Some(package) = &package {
+                    let name = name_of_item_mut(&mut item);
+                    name.identifier = format!("{}.{}", package, name.identifier);
+                }
                 let name = name_of_item(&item);
                 if let Some(existing) = items.insert(name.identifier.clone(), item) {
                     let location = name_of_item(&existing).location;
@@ -45,11 +138,41 @@ fn flatten(context: &mut Context, file: ast::File) -> Result<HashMap<String, ast
 
     let mut items = HashMap::new();
     flatten(context, file, &mut items)?;
+
+    // Qualify names that refer to items in the same package
+    let names = items.keys().cloned().collect::<HashSet<_>>();
+    for item in items.values_mut() {
+        let (package, _) = ast::split_qualified(&name_of_item(item).identifier);
+        if package.is_empty() {
+            continue;
+        }
+        let package = package.join(".");
+        let qualify = |identifiers: Vec<&mut ast::Identifier>| {
+            for identifier in identifiers {
+                let qualified = format!("{}.{}", package, identifier.identifier);
+                if names.contains(&qualified) {
+                    identifier.identifier = qualified;
+                }
+            }
+        };
+        let mut identifiers = Vec::new();
+        for ty in types_of_item_mut(item) {
+            ty.identifiers_mut(&mut identifiers);
+        }
+        qualify(identifiers);
+        let mut identifiers = Vec::new();
+        for expr in exprs_of_item_mut(item) {
+            expr.identifiers_mut(&mut identifiers);
+        }
+        qualify(identifiers);
+    }
     Ok(items)
 }
 
 pub fn type_check(context: &mut Context, file: ast::File) -> Result<Vec<ast::Item>, ()> {
-    let items = flatten(context, file)?;
+    let mut items = flatten(context, file)?;
+    attributes::check(context, &items)?;
+    constants::fold_constants(context, &mut items)?;
     let dependencies = valid::check(context, &items)?;
     sort_items::sort(context, items, dependencies)
 }
@@ -0,0 +1,249 @@
+use crate::parse::{Context, ast};
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use std::collections::HashMap;
+
+/// Something an attribute can be applied to.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Target {
+    Struct,
+    Enum,
+    Flags,
+    Union,
+    Interface,
+    Constant,
+    Alias,
+    Function,
+    Method,
+    Field,
+    Argument,
+}
+
+impl Target {
+    fn of_item(item: &ast::ItemType) -> Self {
+        match item {
+            ast::ItemType::Struct(_) => Self::Struct,
+            ast::ItemType::Enum(_) => Self::Enum,
+            ast::ItemType::Flags(_) => Self::Flags,
+            ast::ItemType::Union(_) => Self::Union,
+            ast::ItemType::Interface(_) => Self::Interface,
+            ast::ItemType::Constant(_) => Self::Constant,
+            ast::ItemType::Alias(_) => Self::Alias,
+            ast::ItemType::Function(f) if f.method_of.is_some() => Self::Method,
+            ast::ItemType::Function(_) => Self::Function,
+            ast::ItemType::Import(_) => unimplemented!(),
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::Struct => "structs",
+            Self::Enum => "enums",
+            Self::Flags => "flags",
+            Self::Union => "unions",
+            Self::Interface => "interfaces",
+            Self::Constant => "constants",
+            Self::Alias => "type aliases",
+            Self::Function => "functions",
+            Self::Method => "functions in `impl` blocks",
+            Self::Field => "fields",
+            Self::Argument => "function arguments",
+        }
+    }
+}
+
+/// The arguments accepted by an attribute.
+enum Args {
+    /// No arguments, e.g. `#[packed]`
+    None,
+    /// A single integer, e.g. `#[align(8)]`
+    Integer,
+    /// An optional string, e.g. `#[deprecated]` or `#[deprecated("use bar")]`
+    OptionalString,
+    /// A string value, e.g. `#[link_name = "x"]`
+    StringValue,
+}
+
+impl Args {
+    fn accepts(&self, args: &ast::AttributeArgs) -> bool {
+        match self {
+            Self::None => matches!(args, ast::AttributeArgs::None),
+            Self::Integer => {
+                matches!(args, ast::AttributeArgs::List(list) if matches!(list.as_slice(), [ast::Literal::Integer(_)]))
+            }
+            Self::OptionalString => match args {
+                ast::AttributeArgs::None => true,
+                ast::AttributeArgs::List(list) => {
+                    matches!(list.as_slice(), [ast::Literal::String(_)])
+                }
+                ast::AttributeArgs::Value(_) => false,
+            },
+            Self::StringValue => {
+                matches!(args, ast::AttributeArgs::Value(ast::Literal::String(_)))
+            }
+        }
+    }
+
+    fn usage(&self, name: &str) -> String {
+        match self {
+            Self::None => format!("`#[{name}]`"),
+            Self::Integer => format!("`#[{name}(...)]` with an integer"),
+            Self::OptionalString => format!("`#[{name}]` or `#[{name}(\"...\")]`"),
+            Self::StringValue => format!("`#[{name} = \"...\"]`"),
+        }
+    }
+}
+
+/// An attribute known to the compiler.
+struct Registered {
+    name: &'static str,
+    args: Args,
+    targets: &'static [Target],
+}
+
+/// Every valid attribute, and where it may be used.
+const REGISTRY: &[Registered] = &[
+    Registered {
+        name: "deprecated",
+        args: Args::OptionalString,
+        targets: &[
+            Target::Struct,
+            Target::Enum,
+            Target::Flags,
+            Target::Union,
+            Target::Interface,
+            Target::Constant,
+            Target::Alias,
+            Target::Function,
+            Target::Method,
+            Target::Field,
+            Target::Argument,
+        ],
+    },
+    Registered {
+        name: "link_name",
+        args: Args::StringValue,
+        targets: &[Target::Function, Target::Method],
+    },
+    Registered {
+        name: "destructor",
+        args: Args::None,
+        targets: &[Target::Method],
+    },
+    Registered {
+        name: "packed",
+        args: Args::None,
+        targets: &[Target::Struct],
+    },
+    Registered {
+        name: "align",
+        args: Args::Integer,
+        targets: &[Target::Struct, Target::Field],
+    },
+];
+
+/// Check that all attributes are known, used in a valid place, and given valid arguments.
+pub fn check(context: &mut Context, items: &HashMap<String, ast::Item>) -> Result<(), ()> {
+    let mut valid = true;
+    for item in items.values() {
+        valid &= check_attributes(context, &item.attributes, Target::of_item(&item.item));
+        match &item.item {
+            ast::ItemType::Struct(s) => {
+                for field in s.fields.iter().flatten() {
+                    valid &= check_attributes(context, &field.attributes, Target::Field);
+                }
+            }
+            ast::ItemType::Function(f) => {
+                for arg in &f.args {
+                    valid &= check_attributes(context, &arg.attributes, Target::Argument);
+                }
+            }
+            ast::ItemType::Interface(i) => {
+                for arg in i.methods.iter().flat_map(|m| &m.function.args) {
+                    valid &= check_attributes(context, &arg.attributes, Target::Argument);
+                }
+            }
+            _ => {}
+        }
+    }
+    if valid { Ok(()) } else { Err(()) }
+}
+
+fn check_attributes(context: &mut Context, attributes: &[ast::Attribute], target: Target) -> bool {
+    let mut valid = true;
+    let mut visited = HashMap::new();
+    for attribute in attributes {
+        let name = &attribute.name;
+        let Some(registered) = REGISTRY.iter().find(|r| r.name == name.identifier) else {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!("unknown attribute `{}`", name.identifier))
+                    .with_labels(vec![Label::primary(
+                        name.location.file_id,
+                        name.location.span.clone(),
+                    )]),
+            );
+            continue;
+        };
+
+        if !registered.targets.contains(&target) {
+            valid = false;
+            let allowed = registered
+                .targets
+                .iter()
+                .map(|t| t.describe())
+                .collect::<Vec<_>>()
+                .join(", ");
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!(
+                        "attribute `{}` cannot be applied to {}",
+                        registered.name,
+                        target.describe()
+                    ))
+                    .with_labels(vec![Label::primary(
+                        attribute.location.file_id,
+                        attribute.location.span.clone(),
+                    )])
+                    .with_notes(vec![format!(
+                        "`{}` applies to: {}",
+                        registered.name, allowed
+                    )]),
+            );
+        }
+
+        if !registered.args.accepts(&attribute.args) {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!("invalid arguments to `{}`", registered.name))
+                    .with_labels(vec![Label::primary(
+                        attribute.location.file_id,
+                        attribute.location.span.clone(),
+                    )])
+                    .with_notes(vec![format!(
+                        "expected {}",
+                        registered.args.usage(registered.name)
+                    )]),
+            );
+        }
+
+        if let Some(existing) = visited.insert(&name.identifier, attribute) {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!(
+                        "attribute `{}` used more than once",
+                        name.identifier
+                    ))
+                    .with_labels(vec![
+                        Label::primary(attribute.location.file_id, attribute.location.span.clone())
+                            .with_message("duplicate attribute"),
+                        Label::secondary(existing.location.file_id, existing.location.span.clone())
+                            .with_message("first used here"),
+                    ]),
+            );
+        }
+    }
+    valid
+}
@@ -0,0 +1,344 @@
+use super::valid::{bits, integer_range};
+use crate::parse::{Context, ast};
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use std::{collections::HashMap, ops::RangeInclusive};
+
+/// Evaluates constant expressions.
+struct Evaluator<'a> {
+    context: &'a mut Context,
+    items: &'a HashMap<String, ast::Item>,
+    /// The value of each named constant, or `None` if it could not be evaluated
+    constants: HashMap<String, Option<i128>>,
+    /// Constants currently being evaluated, to detect cycles
+    stack: Vec<String>,
+    /// The values of earlier flags, while evaluating a set of flags
+    locals: HashMap<String, i128>,
+    /// The bits of any of `locals` that have been used
+    used_locals: i128,
+}
+
+impl Evaluator<'_> {
+    fn report(&mut self, diagnostic: Diagnostic<usize>) -> Option<i128> {
+        self.context.report(&diagnostic);
+        None
+    }
+
+    /// Evaluate the value of a constant definition.
+    fn definition(&mut self, name: &str, constant: &ast::Constant) -> Option<i128> {
+        if let Some(value) = self.constants.get(name) {
+            return *value;
+        }
+        // Constants can't refer to flags
+        let locals = std::mem::take(&mut self.locals);
+        self.stack.push(name.to_string());
+        let value = self.evaluate(&constant.value);
+        self.stack.pop();
+        self.locals = locals;
+        self.constants.insert(name.to_string(), value);
+        value
+    }
+
+    /// Evaluate a reference to a named constant, reporting an error if the name doesn't refer to
+    /// an integer constant.
+    fn constant(&mut self, ident: &ast::Identifier) -> Option<i128> {
+        let name = &ident.identifier;
+        if let Some(value) = self.locals.get(name) {
+            self.used_locals |= *value;
+            return Some(*value);
+        }
+        if self.stack.contains(name) {
+            return self.report(
+                Diagnostic::error()
+                    .with_message(format!("constant `{}` depends on itself", name))
+                    .with_labels(vec![
+                        Label::primary(ident.location.file_id, ident.location.span.clone())
+                            .with_message("used here"),
+                    ]),
+            );
+        }
+
+        let items = self.items;
+        match items.get(name) {
+            Some(ast::Item {
+                item: ast::ItemType::Constant(c),
+                ..
+            }) => {
+                let is_integer = matches!(
+                    &c.ty,
+                    ast::Type::Primitive { primitive, .. } if integer_range(primitive).is_some()
+                );
+                if !is_integer {
+                    return self.report(
+                        Diagnostic::error()
+                            .with_message("expected an integer constant")
+                            .with_labels(vec![
+                                Label::primary(ident.location.file_id, ident.location.span.clone())
+                                    .with_message("used here"),
+                                Label::secondary(
+                                    c.ty.location().file_id,
+                                    c.ty.location().span.clone(),
+                                )
+                                .with_message("constant defined with this type"),
+                            ]),
+                    );
+                }
+
+                self.definition(name, c)
+            }
+            Some(item) => self.report(
+                Diagnostic::error()
+                    .with_message("expected constant")
+                    .with_labels(vec![
+                        Label::primary(ident.location.file_id, ident.location.span.clone())
+                            .with_message("used here"),
+                        Label::secondary(item.location().file_id, item.location().span.clone())
+                            .with_message("defined here"),
+                    ]),
+            ),
+            None => self.report(
+                Diagnostic::error()
+                    .with_message(format!("no constant `{}` found", name))
+                    .with_labels(vec![
+                        Label::primary(ident.location.file_id, ident.location.span.clone())
+                            .with_message("used here"),
+                    ]),
+            ),
+        }
+    }
+
+    fn evaluate(&mut self, expr: &ast::Expr) -> Option<i128> {
+        match expr {
+            ast::Expr::Integer { value, .. } => Some(*value),
+            ast::Expr::Constant(ident) => self.constant(ident),
+            ast::Expr::Binary {
+                location,
+                op,
+                lhs: lhs_expr,
+                rhs: rhs_expr,
+            } => {
+                // Evaluate both sides, so that errors in either are reported
+                let (lhs, rhs) = (self.evaluate(lhs_expr), self.evaluate(rhs_expr));
+                let (lhs, rhs) = (lhs?, rhs?);
+                let value = match op {
+                    ast::BinaryOp::Add => lhs.checked_add(rhs),
+                    ast::BinaryOp::Sub => lhs.checked_sub(rhs),
+                    ast::BinaryOp::Mul => lhs.checked_mul(rhs),
+                    ast::BinaryOp::Div if rhs == 0 => {
+                        let divisor = rhs_expr.location();
+                        return self.report(
+                            Diagnostic::error()
+                                .with_message("division by zero")
+                                .with_labels(vec![
+                                    Label::primary(location.file_id, location.span.clone()),
+                                    Label::secondary(divisor.file_id, divisor.span.clone())
+                                        .with_message("this is zero"),
+                                ]),
+                        );
+                    }
+                    ast::BinaryOp::Div => lhs.checked_div(rhs),
+                    ast::BinaryOp::Shl => u32::try_from(rhs)
+                        .ok()
+                        .and_then(|rhs| lhs.checked_shl(rhs))
+                        .filter(|value| value >> rhs == lhs),
+                    ast::BinaryOp::Or => Some(lhs | rhs),
+                };
+                if value.is_none() {
+                    return self.report(
+                        Diagnostic::error()
+                            .with_message("constant expression overflows")
+                            .with_labels(vec![
+                                Label::primary(location.file_id, location.span.clone())
+                                    .with_message(format!("evaluating {} {} {}", lhs, op, rhs)),
+                            ]),
+                    );
+                }
+                value
+            }
+        }
+    }
+
+    /// Evaluate an expression that must be within a range, such as an array length.
+    fn bounded(
+        &mut self,
+        expr: &ast::Expr,
+        range: RangeInclusive<i128>,
+        what: &str,
+    ) -> Option<i128> {
+        let value = self.evaluate(expr)?;
+        if !range.contains(&value) {
+            let location = expr.location();
+            return self.report(
+                Diagnostic::error()
+                    .with_message(format!("{} out of range", what))
+                    .with_labels(vec![
+                        Label::primary(location.file_id, location.span.clone())
+                            .with_message(format!("has value {}", value)),
+                    ])
+                    .with_notes(vec![format!(
+                        "{}s must be between {} and {}",
+                        what,
+                        range.start(),
+                        range.end()
+                    )]),
+            );
+        }
+        Some(value)
+    }
+
+    /// Evaluate an array length, which must fit in a `usize`.
+    fn array_length(&mut self, len: &ast::Expr) -> Option<i128> {
+        let range = integer_range(&ast::Primitive::Usize).unwrap();
+        self.bounded(len, range, "array length")
+    }
+}
+
+/// Replace an expression with its value.
+fn fold(expr: &mut ast::Expr, value: i128) {
+    *expr = ast::Expr::Integer {
+        location: expr.location().clone(),
+        value,
+    };
+}
+
+/// Evaluate every constant expression, and replace each with its value.
+pub fn fold_constants(
+    context: &mut Context,
+    items: &mut HashMap<String, ast::Item>,
+) -> Result<(), ()> {
+    // Evaluate against an unchanged copy of the items, then fold the expressions in place
+    let snapshot = items.clone();
+    let mut evaluator = Evaluator {
+        context,
+        items: &snapshot,
+        constants: HashMap::new(),
+        stack: Vec::new(),
+        locals: HashMap::new(),
+        used_locals: 0,
+    };
+    let mut valid = true;
+    for (name, item) in items.iter_mut() {
+        match &mut item.item {
+            ast::ItemType::Constant(c) => match evaluator.definition(name, c) {
+                Some(value) => fold(&mut c.value, value),
+                None => valid = false,
+            },
+            ast::ItemType::Enum(e) => {
+                for expr in e.variants.iter_mut().filter_map(|v| v.value.as_mut()) {
+                    match evaluator.evaluate(expr) {
+                        Some(value) => fold(expr, value),
+                        None => valid = false,
+                    }
+                }
+            }
+            ast::ItemType::Flags(f) => valid &= fold_flags(&mut evaluator, f),
+            ast::ItemType::Struct(s) => {
+                if let Some(layout) = &mut s.layout {
+                    // Sizes and alignments are nonzero and fit in a `usize`
+                    let range = 1..=*integer_range(&ast::Primitive::Usize).unwrap().end();
+                    for (expr, what) in
+                        [(&mut layout.size, "size"), (&mut layout.align, "alignment")]
+                    {
+                        match evaluator.bounded(expr, range.clone(), what) {
+                            Some(value) => fold(expr, value),
+                            None => valid = false,
+                        }
+                    }
+                }
+                // Bitfield widths are checked against the field type later
+                for expr in s
+                    .fields
+                    .iter_mut()
+                    .flatten()
+                    .filter_map(|f| f.width.as_mut())
+                {
+                    match evaluator.evaluate(expr) {
+                        Some(value) => fold(expr, value),
+                        None => valid = false,
+                    }
+                }
+            }
+            _ => {}
+        }
+        for ty in super::types_of_item_mut(item) {
+            fold_array_lengths(&mut evaluator, ty, &mut valid);
+        }
+    }
+    if valid { Ok(()) } else { Err(()) }
+}
+
+/// Evaluate and fold the value of each flag, which may refer to earlier flags by name.
+///
+/// Flags written in terms of earlier flags are explicit combinations, which may overlap them. Any
+/// other bits, including bits a combination adds to the flags it names, must not be shared.
+fn fold_flags(evaluator: &mut Evaluator, flags: &mut ast::Flags) -> bool {
+    let mut valid = true;
+    // The location, value, and bits not taken from earlier flags of each flag
+    let mut distinct: Vec<(ast::Location, i128, i128)> = Vec::new();
+    for flag in &mut flags.flags {
+        evaluator.used_locals = 0;
+        let Some(value) = evaluator.evaluate(&flag.value) else {
+            valid = false;
+            continue;
+        };
+        let new_bits = value & !evaluator.used_locals;
+        if new_bits != 0 {
+            if let Some((location, existing, _)) =
+                distinct.iter().find(|(_, _, bits)| bits & new_bits != 0)
+            {
+                valid = false;
+                evaluator.report(
+                    Diagnostic::error()
+                        .with_message(format!("flag `{}` overlaps another flag", flag.name.identifier))
+                        .with_labels(vec![
+                            Label::primary(flag.location.file_id, flag.location.span.clone())
+                                .with_message(format!("has value {}", bits(value))),
+                            Label::secondary(location.file_id, location.span.clone())
+                                .with_message(format!("has value {}", bits(*existing))),
+                        ])
+                        .with_notes(vec![
+                            "combinations of flags must be written in terms of them, such as `READ | WRITE`"
+                                .to_string(),
+                        ]),
+                );
+            }
+            distinct.push((flag.location.clone(), value, new_bits));
+        }
+        evaluator.locals.insert(flag.name.identifier.clone(), value);
+        fold(&mut flag.value, value);
+    }
+    evaluator.locals.clear();
+    valid
+}
+
+/// Fold the lengths of any arrays in this type.
+///
+/// Lengths that only name a constant are kept, so that generated code refers to the constant.
+fn fold_array_lengths(evaluator: &mut Evaluator, ty: &mut ast::Type, valid: &mut bool) {
+    match ty {
+        ast::Type::Primitive { .. } | ast::Type::Void { .. } | ast::Type::String(_) => {}
+        ast::Type::Identifier(_) => {}
+        ast::Type::Pointer(p) | ast::Type::OptionalPointer(p) => {
+            fold_array_lengths(evaluator, &mut p.ty, valid)
+        }
+        ast::Type::Slice(s) => fold_array_lengths(evaluator, &mut s.ty, valid),
+        ast::Type::Owned(o) => fold_array_lengths(evaluator, &mut o.ty, valid),
+        ast::Type::Shared(s) => fold_array_lengths(evaluator, &mut s.ty, valid),
+        ast::Type::Optional(o) => fold_array_lengths(evaluator, &mut o.ty, valid),
+        ast::Type::FunctionPointer(f) => {
+            for (_, ty) in f.args.iter_mut() {
+                fold_array_lengths(evaluator, ty, valid);
+            }
+            if let Some(ty) = f.returns.as_mut() {
+                fold_array_lengths(evaluator, ty, valid);
+            }
+        }
+        ast::Type::Array(a) => {
+            fold_array_lengths(evaluator, &mut a.ty, valid);
+            match evaluator.array_length(&a.len) {
+                Some(_) if matches!(a.len, ast::Expr::Constant(_)) => {}
+                Some(value) => fold(&mut a.len, value),
+                None => *valid = false,
+            }
+        }
+    }
+}
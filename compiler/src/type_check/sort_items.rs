@@ -15,7 +15,13 @@ pub fn sort(
     let mut name_to_index = HashMap::new();
     let mut index_to_name = HashMap::new();
 
-    for name in dependencies.keys() {
+    // Nodes are added in reverse source order, since the sort reverses independent items
+    let mut names = dependencies.keys().collect::<Vec<_>>();
+    names.sort_by_key(|name| {
+        let location = items[*name].location();
+        std::cmp::Reverse((location.file_id, location.span.start))
+    });
+    for name in names {
         let index = graph.add_node(name.clone());
         name_to_index.insert(name.clone(), index);
         index_to_name.insert(index, name.clone());
@@ -50,6 +56,7 @@ pub fn sort(
                             .with_message("this type contains itself"),
                     ]),
             );
+            return Err(());
         }
         Ok(order) => {
             for index in order {
@@ -57,7 +64,13 @@ pub fn sort(
             }
         }
     }
-    sorted.extend(items.into_iter().map(|(_, v)| v));
+    // Items without dependencies follow in source order
+    let mut rest = items.into_values().collect::<Vec<_>>();
+    rest.sort_by_key(|item| {
+        let location = item.location();
+        (location.file_id, location.span.start)
+    });
+    sorted.extend(rest);
 
     Ok(sorted)
 }
@@ -1,42 +1,90 @@
 use crate::parse::{Context, ast};
 use codespan_reporting::diagnostic::{Diagnostic, Label};
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::RangeInclusive};
 
 /// Append any items to this list that affect the layout of the queried type
-fn add_layout_deps(ty: &ast::Type, mut deps: &mut Vec<String>) {
+fn add_layout_deps(ty: &ast::Type, deps: &mut Vec<String>) {
     match &ty {
         ast::Type::Primitive {
             location: _,
             primitive: _,
         } => {}
-        ast::Type::Pointer(_) => {}
+        ast::Type::Void { .. } => {}
+        ast::Type::Pointer(_) | ast::Type::OptionalPointer(_) => {}
         ast::Type::String(_) => {}
         ast::Type::Slice(_) => {}
         ast::Type::Owned(_) => {}
+        ast::Type::Shared(_) => {}
         ast::Type::FunctionPointer(_) => {}
-        ast::Type::Array(a) => add_layout_deps(a.ty.as_ref(), &mut deps),
+        ast::Type::Array(a) => add_layout_deps(a.ty.as_ref(), deps),
+        ast::Type::Optional(o) => add_layout_deps(o.ty.as_ref(), deps),
         ast::Type::Identifier(ident) => deps.push(ident.identifier.clone()),
     }
 }
 
+/// Append any aliases referenced by this type, which must be declared before the type is used
+///
+/// The values of optional types are also included, even behind pointers, since each optional type
+/// is declared in C as a struct containing its value.
+fn add_alias_deps(ty: &ast::Type, items: &HashMap<String, ast::Item>, deps: &mut Vec<String>) {
+    for ty in ty.iter_tree() {
+        if let ast::Type::Identifier(ident) = ty
+            && let Some(ast::Item {
+                item: ast::ItemType::Alias(_),
+                ..
+            }) = items.get(&ident.identifier)
+        {
+            deps.push(ident.identifier.clone());
+        }
+        if let ast::Type::Optional(o) = ty {
+            add_layout_deps(&o.ty, deps);
+        }
+    }
+}
+
 /// Check basic type properties
 fn type_checks(context: &mut Context, items: &HashMap<String, ast::Item>) -> Result<(), ()> {
     // Flatten the type tree
     let mut types = Vec::new();
-    for (_, item) in items {
+    for item in items.values() {
         match &item.item {
             ast::ItemType::Import(_) => unreachable!("imports should have been resolved"),
             ast::ItemType::Struct(s) => {
+                if let Some(base) = &s.base {
+                    types.extend(base.iter_tree());
+                }
                 if let Some(fields) = &s.fields {
                     for field in fields.iter() {
                         types.extend(field.ty.iter_tree());
                     }
                 }
             }
-            ast::ItemType::Function(f) => {
-                for (_, ty) in f.args.iter() {
+            ast::ItemType::Enum(_) => {}
+            ast::ItemType::Flags(_) => {}
+            ast::ItemType::Constant(_) => {}
+            ast::ItemType::Alias(a) => types.extend(a.ty.iter_tree()),
+            ast::ItemType::Union(u) => {
+                for ty in u.variants.iter().filter_map(|v| v.ty.as_ref()) {
                     types.extend(ty.iter_tree());
                 }
+            }
+            ast::ItemType::Interface(i) => {
+                for f in i.methods.iter().map(|m| &m.function) {
+                    for arg in f.args.iter() {
+                        types.extend(arg.ty.iter_tree());
+                    }
+                    if let Some(returns) = &f.returns {
+                        types.extend(returns.iter_tree())
+                    }
+                }
+            }
+            ast::ItemType::Function(f) => {
+                if let Some(method_of) = &f.method_of {
+                    types.extend(method_of.ty.iter_tree());
+                }
+                for arg in f.args.iter() {
+                    types.extend(arg.ty.iter_tree());
+                }
                 if let Some(returns) = &f.returns {
                     types.extend(returns.iter_tree())
                 }
@@ -52,8 +100,14 @@ fn type_checks(context: &mut Context, items: &HashMap<String, ast::Item>) -> Res
             if let Some(item) = items.get(&ident.identifier) {
                 let bad_item = match &item.item {
                     ast::ItemType::Struct(_) => None,
+                    ast::ItemType::Enum(_) => None,
+                    ast::ItemType::Flags(_) => None,
+                    ast::ItemType::Union(_) => None,
+                    ast::ItemType::Interface(_) => None,
+                    ast::ItemType::Alias(_) => None,
                     ast::ItemType::Import(_) => unreachable!("imports should have been resolved"),
                     ast::ItemType::Function(f) => Some(("function", f.location.clone())),
+                    ast::ItemType::Constant(c) => Some(("constant", c.location.clone())),
                 };
                 if let Some((bad_item_name, bad_item_loc)) = bad_item {
                     context.report(
@@ -85,6 +139,62 @@ fn type_checks(context: &mut Context, items: &HashMap<String, ast::Item>) -> Res
         return Err(());
     }
 
+    // Check that aliases don't name themselves
+    let mut bad_alias = false;
+    for (name, item) in items {
+        if let ast::ItemType::Alias(a) = &item.item {
+            let mut visited = vec![name];
+            let mut ty = &a.ty;
+            while let ast::Type::Identifier(ident) = ty
+                && let Some(ast::Item {
+                    item: ast::ItemType::Alias(next),
+                    ..
+                }) = items.get(&ident.identifier)
+            {
+                if visited.contains(&&ident.identifier) {
+                    if &ident.identifier == name {
+                        context.report(
+                            &Diagnostic::error()
+                                .with_message(format!("alias `{}` refers to itself", name))
+                                .with_labels(vec![Label::primary(
+                                    a.location.file_id,
+                                    a.location.span.clone(),
+                                )]),
+                        );
+                        bad_alias = true;
+                    }
+                    break;
+                }
+                visited.push(&ident.identifier);
+                ty = &next.ty;
+            }
+        }
+    }
+    if bad_alias {
+        return Err(());
+    }
+
+    // Check that shared pointers are pointers, since they're retained and released through them
+    let mut bad_shared = false;
+    for ty in &types {
+        if let ast::Type::Shared(s) = ty
+            && !matches!(s.ty.as_ref(), ast::Type::Pointer(_))
+        {
+            context.report(
+                &Diagnostic::error()
+                    .with_message("`shared` must be applied to a pointer")
+                    .with_labels(vec![
+                        Label::primary(s.ty.location().file_id, s.ty.location().span.clone())
+                            .with_message("expected `const * T` or `mut * T`"),
+                    ]),
+            );
+            bad_shared = true;
+        }
+    }
+    if bad_shared {
+        return Err(());
+    }
+
     // Check proper sizedness of all types
     let mut bad_sized = false;
     for ty in &types {
@@ -93,13 +203,15 @@ fn type_checks(context: &mut Context, items: &HashMap<String, ast::Item>) -> Res
                 location: _,
                 primitive: _,
             } => {}
-            ast::Type::Pointer(_) => {}
+            ast::Type::Void { .. } => {}
+            ast::Type::Pointer(_) | ast::Type::OptionalPointer(_) => {}
             ast::Type::String(_) => {}
             ast::Type::Slice(_) => {}
             ast::Type::Owned(_) => {}
+            ast::Type::Shared(_) => {}
             ast::Type::FunctionPointer(_) => {}
             ast::Type::Array(a) => {
-                if !is_sized(a.ty.as_ref(), &items) {
+                if !is_sized(a.ty.as_ref(), items) {
                     context.report(
                         &Diagnostic::error()
                             .with_message("array element must be a sized type")
@@ -111,6 +223,22 @@ fn type_checks(context: &mut Context, items: &HashMap<String, ast::Item>) -> Res
                     bad_sized = true;
                 }
             }
+            ast::Type::Optional(o) => {
+                if !is_sized(o.ty.as_ref(), items) {
+                    context.report(
+                        &Diagnostic::error()
+                            .with_message("optional value must be a sized type")
+                            .with_labels(vec![
+                                Label::primary(
+                                    o.ty.location().file_id,
+                                    o.ty.location().span.clone(),
+                                )
+                                .with_message(unsized_reason(&o.ty, items)),
+                            ]),
+                    );
+                    bad_sized = true;
+                }
+            }
             ast::Type::Identifier(_) => {}
         }
     }
@@ -130,16 +258,24 @@ fn is_sized(ty: &ast::Type, items: &HashMap<String, ast::Item>) -> bool {
             location: _,
             primitive: _,
         } => true,
-        ast::Type::Pointer(_) => true,
+        ast::Type::Void { .. } => false,
+        ast::Type::Pointer(_) | ast::Type::OptionalPointer(_) => true,
         ast::Type::String(_) => true,
         ast::Type::Slice(_) => true,
         ast::Type::Owned(_) => true,
+        ast::Type::Shared(_) => true,
         ast::Type::FunctionPointer(_) => true,
         ast::Type::Array(_) => true,
+        ast::Type::Optional(_) => true,
         ast::Type::Identifier(ident) => {
             if let Some(item) = items.get(&ident.identifier) {
                 match &item.item {
-                    ast::ItemType::Struct(s) => s.fields.is_some(),
+                    ast::ItemType::Struct(s) => s.fields.is_some() || s.layout.is_some(),
+                    ast::ItemType::Enum(_) => true,
+                    ast::ItemType::Flags(_) => true,
+                    ast::ItemType::Union(_) => true,
+                    ast::ItemType::Interface(_) => true,
+                    ast::ItemType::Alias(a) => is_sized(&a.ty, items),
                     _ => unreachable!("ident check should have caught this"),
                 }
             } else {
@@ -149,6 +285,622 @@ fn is_sized(ty: &ast::Type, items: &HashMap<String, ast::Item>) -> bool {
     }
 }
 
+/// Explains why a type that failed `is_sized` is unsized.
+fn unsized_reason(ty: &ast::Type, items: &HashMap<String, ast::Item>) -> &'static str {
+    match ty {
+        ast::Type::Void { .. } => "`void` has no size, but can be referenced through pointers",
+        ast::Type::Identifier(ident) => match items.get(&ident.identifier).map(|item| &item.item) {
+            Some(ast::ItemType::Alias(a)) => unsized_reason(&a.ty, items),
+            _ => {
+                "structs without fields or a size are not sized, but can be referenced through pointers"
+            }
+        },
+        _ => {
+            "structs without fields or a size are not sized, but can be referenced through pointers"
+        }
+    }
+}
+
+/// Returns the range of values representable by an integer primitive.
+///
+/// Pointer-sized integers and the platform C types are limited to the values they can hold on
+/// every target, and `u128` is limited to the values of an `i128`.
+pub(super) fn integer_range(primitive: &ast::Primitive) -> Option<RangeInclusive<i128>> {
+    match primitive {
+        ast::Primitive::I8 => Some(i8::MIN.into()..=i8::MAX.into()),
+        ast::Primitive::I16 => Some(i16::MIN.into()..=i16::MAX.into()),
+        ast::Primitive::I32
+        | ast::Primitive::Isize
+        | ast::Primitive::CInt
+        | ast::Primitive::CLong => Some(i32::MIN.into()..=i32::MAX.into()),
+        ast::Primitive::I64 => Some(i64::MIN.into()..=i64::MAX.into()),
+        ast::Primitive::I128 => Some(i128::MIN..=i128::MAX),
+        ast::Primitive::U8 => Some(0..=u8::MAX.into()),
+        ast::Primitive::U16 => Some(0..=u16::MAX.into()),
+        ast::Primitive::U32 | ast::Primitive::Usize | ast::Primitive::CUlong => {
+            Some(0..=u32::MAX.into())
+        }
+        ast::Primitive::U64 => Some(0..=u64::MAX.into()),
+        ast::Primitive::U128 => Some(0..=i128::MAX),
+        ast::Primitive::CChar => Some(0..=i8::MAX.into()),
+        ast::Primitive::Bool | ast::Primitive::F32 | ast::Primitive::F64 => None,
+    }
+}
+
+/// Check that `packed` and `align` attributes describe a valid layout.
+fn check_layout_attributes(context: &mut Context, item: &ast::Item, s: &ast::Struct) -> bool {
+    let mut valid = true;
+    let field_attributes = s.fields.iter().flatten().map(|f| &f.attributes);
+    for attribute in std::iter::once(&item.attributes)
+        .chain(field_attributes)
+        .flatten()
+    {
+        if attribute.name.identifier == "align"
+            && !attribute.integer().is_some_and(u64::is_power_of_two)
+        {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message("alignment must be a power of two")
+                    .with_labels(vec![Label::primary(
+                        attribute.location.file_id,
+                        attribute.location.span.clone(),
+                    )]),
+            );
+        }
+    }
+
+    if s.fields.is_none() {
+        for name in ["packed", "align"] {
+            if let Some(attribute) = ast::find_attribute(&item.attributes, name) {
+                valid = false;
+                context.report(
+                    &Diagnostic::error()
+                        .with_message(format!("`{}` requires a struct with fields", name))
+                        .with_labels(vec![
+                            Label::primary(
+                                attribute.location.file_id,
+                                attribute.location.span.clone(),
+                            ),
+                            Label::secondary(s.location.file_id, s.location.span.clone())
+                                .with_message("struct has no fields, so its layout is unknown"),
+                        ]),
+                );
+            }
+        }
+    }
+    valid
+}
+
+/// Check that the size and alignment of an opaque struct describe a valid layout.
+fn check_opaque_layout(context: &mut Context, layout: &ast::OpaqueLayout) -> bool {
+    let size = layout.size.value().expect("sizes are folded");
+    let align = layout.align.value().expect("alignments are folded");
+    let location = layout.align.location();
+    if !u64::try_from(align).is_ok_and(u64::is_power_of_two) {
+        context.report(
+            &Diagnostic::error()
+                .with_message("alignment must be a power of two")
+                .with_labels(vec![
+                    Label::primary(location.file_id, location.span.clone())
+                        .with_message(format!("has value {}", align)),
+                ]),
+        );
+        false
+    } else if size % align != 0 {
+        context.report(
+            &Diagnostic::error()
+                .with_message("size must be a multiple of the alignment")
+                .with_labels(vec![
+                    Label::primary(layout.location.file_id, layout.location.span.clone())
+                        .with_message(format!("size {} is not a multiple of {}", size, align)),
+                ]),
+        );
+        false
+    } else {
+        true
+    }
+}
+
+/// Returns the struct named by a type, looking through aliases.
+fn resolve_struct<'a>(
+    ty: &ast::Type,
+    items: &'a HashMap<String, ast::Item>,
+) -> Option<&'a ast::Struct> {
+    match ty {
+        ast::Type::Identifier(ident) => match &items.get(&ident.identifier)?.item {
+            ast::ItemType::Struct(s) => Some(s),
+            ast::ItemType::Alias(a) => resolve_struct(&a.ty, items),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the fields of every base of a struct, stopping at any cycle.
+///
+/// Cycles are reported when the items are sorted.
+fn inherited_fields<'a>(
+    s: &'a ast::Struct,
+    items: &'a HashMap<String, ast::Item>,
+) -> Vec<&'a ast::Field> {
+    let mut fields = Vec::new();
+    let mut visited = vec![&s.name.identifier];
+    let mut base = s.base.as_ref().and_then(|base| resolve_struct(base, items));
+    while let Some(s) = base {
+        if visited.contains(&&s.name.identifier) {
+            break;
+        }
+        visited.push(&s.name.identifier);
+        fields.extend(s.fields.iter().flatten());
+        base = s.base.as_ref().and_then(|base| resolve_struct(base, items));
+    }
+    fields
+}
+
+/// Check that the base of a struct is a struct with fields, and that the fields of the struct
+/// don't conflict with it.
+fn check_base(
+    context: &mut Context,
+    items: &HashMap<String, ast::Item>,
+    item: &ast::Item,
+    s: &ast::Struct,
+    base: &ast::Type,
+) -> bool {
+    let location = base.location();
+    if resolve_struct(base, items).is_none_or(|base| base.fields.is_none()) {
+        context.report(
+            &Diagnostic::error()
+                .with_message("base must be a struct with fields")
+                .with_labels(vec![
+                    Label::primary(location.file_id, location.span.clone())
+                        .with_message("used as a base here"),
+                ]),
+        );
+        return false;
+    }
+
+    let mut valid = true;
+    let Some(fields) = &s.fields else {
+        context.report(
+            &Diagnostic::error()
+                .with_message("a struct with a base must have fields")
+                .with_labels(vec![
+                    Label::primary(s.location.file_id, s.location.span.clone()),
+                    Label::secondary(location.file_id, location.span.clone())
+                        .with_message("base declared here"),
+                ]),
+        );
+        return false;
+    };
+
+    if let Some(packed) = ast::find_attribute(&item.attributes, "packed") {
+        valid = false;
+        context.report(
+            &Diagnostic::error()
+                .with_message("`packed` can't be used with a base struct")
+                .with_labels(vec![
+                    Label::primary(packed.location.file_id, packed.location.span.clone()),
+                    Label::secondary(location.file_id, location.span.clone())
+                        .with_message("base declared here"),
+                ]),
+        );
+    }
+
+    let inherited = inherited_fields(s, items);
+    for field in fields {
+        let name = &field.name;
+        if name.identifier == "base" {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message("field `base` conflicts with the base struct")
+                    .with_labels(vec![
+                        Label::primary(name.location.file_id, name.location.span.clone()),
+                        Label::secondary(location.file_id, location.span.clone())
+                            .with_message("base declared here"),
+                    ])
+                    .with_notes(vec![
+                        "in C, the base struct is the first field, named `base`".to_string(),
+                    ]),
+            );
+        } else if let Some(existing) = inherited
+            .iter()
+            .find(|f| f.name.identifier == name.identifier)
+        {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!(
+                        "field `{}` is already defined by a base struct",
+                        name.identifier
+                    ))
+                    .with_labels(vec![
+                        Label::primary(name.location.file_id, name.location.span.clone())
+                            .with_message("defined again here"),
+                        Label::secondary(
+                            existing.name.location.file_id,
+                            existing.name.location.span.clone(),
+                        )
+                        .with_message("first defined here"),
+                    ]),
+            );
+        }
+    }
+    valid
+}
+
+/// Returns the width in bits of a type that can be used in a bitfield.
+///
+/// Enums and flags have the width of their representation. Pointer-sized integers and the
+/// platform C types can't be used, since their width varies between targets.
+fn bitfield_type_width(ty: &ast::Type, items: &HashMap<String, ast::Item>) -> Option<i128> {
+    match ty {
+        ast::Type::Primitive { primitive, .. } => match primitive {
+            ast::Primitive::Bool => Some(1),
+            ast::Primitive::I8 | ast::Primitive::U8 => Some(8),
+            ast::Primitive::I16 | ast::Primitive::U16 => Some(16),
+            ast::Primitive::I32 | ast::Primitive::U32 => Some(32),
+            ast::Primitive::I64 | ast::Primitive::U64 => Some(64),
+            _ => None,
+        },
+        ast::Type::Identifier(ident) => match &items.get(&ident.identifier)?.item {
+            ast::ItemType::Enum(e) => bitfield_type_width(&e.repr, items),
+            ast::ItemType::Flags(f) => bitfield_type_width(&f.repr, items),
+            ast::ItemType::Alias(a) => bitfield_type_width(&a.ty, items),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Check that a bitfield has a suitable type, and fits within it.
+fn check_bitfield(
+    context: &mut Context,
+    items: &HashMap<String, ast::Item>,
+    item: &ast::Item,
+    field: &ast::Field,
+    width: &ast::Expr,
+) -> bool {
+    let mut valid = true;
+    let ty = field.ty.location();
+    let location = width.location();
+    let value = width.value().expect("widths are folded");
+    match bitfield_type_width(&field.ty, items) {
+        None => {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message("bitfield must have an integer, enum, or flags type")
+                    .with_labels(vec![
+                        Label::primary(ty.file_id, ty.span.clone()),
+                        Label::secondary(location.file_id, location.span.clone())
+                            .with_message("declared as a bitfield here"),
+                    ])
+                    .with_notes(vec![
+                        "pointer-sized integers and platform C types vary in width, and integers may have at most 64 bits"
+                            .to_string(),
+                    ]),
+            );
+        }
+        Some(bits) if !(1..=bits).contains(&value) => {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message("bitfield width out of range")
+                    .with_labels(vec![
+                        Label::primary(location.file_id, location.span.clone())
+                            .with_message(format!("has value {}", value)),
+                        Label::secondary(ty.file_id, ty.span.clone()).with_message(if bits == 1 {
+                            "has 1 bit".to_string()
+                        } else {
+                            format!("has {} bits", bits)
+                        }),
+                    ])
+                    .with_notes(vec![if bits == 1 {
+                        "bitfields of this type must have a width of 1".to_string()
+                    } else {
+                        format!("bitfield widths must be between 1 and {}", bits)
+                    }]),
+            );
+        }
+        Some(_) => {}
+    }
+
+    for (attributes, name) in [(&field.attributes, "align"), (&item.attributes, "packed")] {
+        if let Some(attribute) = ast::find_attribute(attributes, name) {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!("`{}` can't be used with bitfields", name))
+                    .with_labels(vec![
+                        Label::primary(attribute.location.file_id, attribute.location.span.clone()),
+                        Label::secondary(location.file_id, location.span.clone())
+                            .with_message("declared as a bitfield here"),
+                    ]),
+            );
+        }
+    }
+    valid
+}
+
+/// Check that the methods of an interface have unique names and valid signatures, and that
+/// nothing conflicts with the object, which is named `self`.
+fn check_interface(
+    context: &mut Context,
+    items: &HashMap<String, ast::Item>,
+    i: &ast::Interface,
+) -> bool {
+    let mut valid = true;
+    let mut visited_methods = HashMap::new();
+    for f in i.methods.iter().map(|m| &m.function) {
+        let name = &f.name;
+        if let Some(existing) = visited_methods.insert(&name.identifier, name) {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message("method names must be unique")
+                    .with_labels(vec![
+                        Label::primary(name.location.file_id, name.location.span.clone())
+                            .with_message("duplicate method name"),
+                        Label::secondary(existing.location.file_id, existing.location.span.clone())
+                            .with_message("first used here"),
+                    ]),
+            );
+        }
+
+        for name in std::iter::once(name).chain(f.args.iter().map(|arg| &arg.name)) {
+            if name.identifier == "self" {
+                valid = false;
+                context.report(
+                    &Diagnostic::error()
+                        .with_message("`self` conflicts with the object implementing the interface")
+                        .with_labels(vec![Label::primary(
+                            name.location.file_id,
+                            name.location.span.clone(),
+                        )])
+                        .with_notes(vec![
+                            "the object is stored in the interface as `self`, and passed to each method as `self`"
+                                .to_string(),
+                        ]),
+                );
+            }
+        }
+
+        valid &= check_signature(context, items, f);
+    }
+    valid
+}
+
+/// Check that an `impl` block is for a struct, and that any `self` argument is a pointer to it.
+fn check_method(
+    context: &mut Context,
+    items: &HashMap<String, ast::Item>,
+    f: &ast::Function,
+    method_of: &ast::MethodOf,
+) -> bool {
+    let ast::Type::Identifier(ty) = &method_of.ty else {
+        unreachable!("`impl` blocks are for named types");
+    };
+    let mut valid = true;
+    if !matches!(
+        items.get(&ty.identifier).map(|item| &item.item),
+        Some(ast::ItemType::Struct(_))
+    ) {
+        valid = false;
+        context.report(
+            &Diagnostic::error()
+                .with_message("`impl` must be for a struct")
+                .with_labels(vec![
+                    Label::primary(ty.location.file_id, ty.location.span.clone())
+                        .with_message("not a struct"),
+                ]),
+        );
+    }
+
+    for (index, arg) in f.args.iter().enumerate() {
+        if arg.name.identifier != "self" {
+            continue;
+        }
+        let name = &arg.name;
+        if index != 0 {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message("`self` must be the first argument")
+                    .with_labels(vec![Label::primary(
+                        name.location.file_id,
+                        name.location.span.clone(),
+                    )]),
+            );
+        }
+        let is_pointer_to_self = match &arg.ty {
+            ast::Type::Pointer(p) => matches!(
+                p.ty.as_ref(),
+                ast::Type::Identifier(i) if i.identifier == ty.identifier
+            ),
+            _ => false,
+        };
+        if !is_pointer_to_self {
+            valid = false;
+            let location = arg.ty.location();
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!("`self` must be a pointer to `{}`", ty.identifier))
+                    .with_labels(vec![
+                        Label::primary(location.file_id, location.span.clone()).with_message(
+                            format!(
+                                "expected `const * {0}` or `mut * {0}`",
+                                ast::split_qualified(&ty.identifier).1
+                            ),
+                        ),
+                    ]),
+            );
+        }
+    }
+    valid
+}
+
+/// Check that a destructor is for an abstract struct, and only takes `self`.
+fn check_destructor(
+    context: &mut Context,
+    items: &HashMap<String, ast::Item>,
+    f: &ast::Function,
+    method_of: &ast::MethodOf,
+) -> bool {
+    let ast::Type::Identifier(ty) = &method_of.ty else {
+        unreachable!("`impl` blocks are for named types");
+    };
+    let mut valid = true;
+    if let Some(ast::ItemType::Struct(s)) = items.get(&ty.identifier).map(|item| &item.item)
+        && (s.fields.is_some() || s.layout.is_some())
+    {
+        valid = false;
+        context.report(
+            &Diagnostic::error()
+                .with_message("destructors are only for abstract structs")
+                .with_labels(vec![
+                    Label::primary(ty.location.file_id, ty.location.span.clone())
+                        .with_message("not an abstract struct"),
+                ])
+                .with_notes(vec![
+                    "structs with fields or a size are released by their owners".to_string(),
+                ]),
+        );
+    }
+
+    let takes_self = matches!(
+        f.args.as_slice(),
+        [ast::Argument { name, ty: ast::Type::Pointer(p), .. }]
+            if name.identifier == "self" && matches!(p.modifier, ast::PointerModifier::Mut)
+    );
+    if !takes_self || f.returns.is_some() {
+        valid = false;
+        let name = &method_of.name;
+        context.report(
+            &Diagnostic::error()
+                .with_message("destructor must only take `self`, and return `void`")
+                .with_labels(vec![Label::primary(
+                    name.location.file_id,
+                    name.location.span.clone(),
+                )])
+                .with_notes(vec![format!(
+                    "expected `function {}(self: mut * {}) -> void;`",
+                    name.identifier,
+                    ast::split_qualified(&ty.identifier).1
+                )]),
+        );
+    }
+    valid
+}
+
+/// Check that each struct has at most one destructor.
+fn check_unique_destructors(context: &mut Context, items: &HashMap<String, ast::Item>) -> bool {
+    let mut destructors = items
+        .values()
+        .filter(|item| ast::find_attribute(&item.attributes, "destructor").is_some())
+        .filter_map(|item| match &item.item {
+            ast::ItemType::Function(f) => f.method_of.as_ref(),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    destructors.sort_by_key(|method_of| {
+        let location = &method_of.name.location;
+        (location.file_id, location.span.start)
+    });
+
+    let mut valid = true;
+    let mut visited: HashMap<&String, &ast::Identifier> = HashMap::new();
+    for method_of in destructors {
+        let ast::Type::Identifier(ty) = &method_of.ty else {
+            unreachable!("`impl` blocks are for named types");
+        };
+        let name = &method_of.name;
+        if let Some(existing) = visited.get(&ty.identifier) {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message(format!("`{}` already has a destructor", ty.identifier))
+                    .with_labels(vec![
+                        Label::primary(name.location.file_id, name.location.span.clone())
+                            .with_message("second destructor"),
+                        Label::secondary(existing.location.file_id, existing.location.span.clone())
+                            .with_message("first destructor"),
+                    ]),
+            );
+        } else {
+            visited.insert(&ty.identifier, name);
+        }
+    }
+    valid
+}
+
+/// Check that the arguments of a function or method have unique names, and that its argument and
+/// return types are sized.
+fn check_signature(
+    context: &mut Context,
+    items: &HashMap<String, ast::Item>,
+    f: &ast::Function,
+) -> bool {
+    let mut valid = true;
+    let mut visited_args = HashMap::new();
+    for ast::Argument { name, ty, .. } in &f.args {
+        // Check that the argument name is unique
+        if let Some(existing_arg) = visited_args.insert(&name.identifier, name) {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message("argument name must be unique")
+                    .with_labels(vec![
+                        Label::primary(name.location.file_id, name.location.span.clone())
+                            .with_message("duplicate argument name"),
+                        Label::secondary(
+                            existing_arg.location.file_id,
+                            existing_arg.location.span.clone(),
+                        )
+                        .with_message("first used here"),
+                    ]),
+            );
+        }
+
+        if !is_sized(ty, items) {
+            valid = false;
+            context.report(
+                &Diagnostic::error()
+                    .with_message("argument is not a sized type")
+                    .with_labels(vec![
+                        Label::primary(ty.location().file_id, ty.location().span.clone())
+                            .with_message(unsized_reason(ty, items)),
+                    ]),
+            );
+        }
+    }
+
+    if let Some(returns) = &f.returns
+        && !is_sized(returns, items)
+    {
+        valid = false;
+        context.report(
+            &Diagnostic::error()
+                .with_message("return type is not a sized type")
+                .with_labels(vec![
+                    Label::primary(returns.location().file_id, returns.location().span.clone())
+                        .with_message(unsized_reason(returns, items)),
+                ]),
+        );
+    }
+    valid
+}
+
+/// Format a flag value, in hexadecimal unless it's negative.
+pub(super) fn bits(value: i128) -> String {
+    if value < 0 {
+        value.to_string()
+    } else {
+        format!("{:#x}", value)
+    }
+}
+
 /// Assert that types only reference concrete types by value.
 ///
 /// Abstract types can be referenced only by pointer.
@@ -160,23 +912,36 @@ pub fn check(
 ) -> Result<HashMap<String, Vec<String>>, ()> {
     type_checks(context, items)?;
 
+    let mut valid = true;
     let mut dependencies = HashMap::new();
 
     for (name, item) in items {
         match &item.item {
             ast::ItemType::Import(_) => unimplemented!(),
             ast::ItemType::Struct(s) => {
+                valid &= check_layout_attributes(context, item, s);
+                if let Some(layout) = &s.layout {
+                    valid &= check_opaque_layout(context, layout);
+                }
+                if let Some(base) = &s.base {
+                    valid &= check_base(context, items, item, s, base);
+                }
                 if let Some(fields) = &s.fields {
                     // Check the following:
                     // * Fields must have unique names
                     // * Field types must be sized
                     let mut visited_fields = HashMap::new();
                     let mut these_dependencies = Vec::new();
+                    if let Some(base) = &s.base {
+                        add_layout_deps(base, &mut these_dependencies);
+                        add_alias_deps(base, items, &mut these_dependencies);
+                    }
                     for field in fields {
                         // Check that the field name is unique
                         if let Some(existing_field) =
                             visited_fields.insert(&field.name.identifier, &field.name)
                         {
+                            valid = false;
                             context.report(
                                 &Diagnostic::error()
                                     .with_message("field names must be unique")
@@ -195,7 +960,8 @@ pub fn check(
                             );
                         }
 
-                        if !is_sized(&field.ty, &items) {
+                        if !is_sized(&field.ty, items) {
+                            valid = false;
                             context.report(
                                 &Diagnostic::error()
                                     .with_message("field is not a sized type")
@@ -204,82 +970,331 @@ pub fn check(
                                             field.ty.location().file_id,
                                             field.ty.location().span.clone(),
                                         )
-                                        .with_message("structs without fields are not sized, but can be referenced through pointers"),
+                                        .with_message(unsized_reason(&field.ty, items)),
                                     ]),
                             );
                         }
 
+                        if let Some(width) = &field.width {
+                            valid &= check_bitfield(context, items, item, field, width);
+                        }
+
                         add_layout_deps(&field.ty, &mut these_dependencies);
+                        add_alias_deps(&field.ty, items, &mut these_dependencies);
                     }
                     dependencies.insert(name.clone(), these_dependencies);
+                } else if s.layout.is_some() {
+                    // Opaque structs have a known layout, but no fields to depend on
+                    dependencies.insert(name.clone(), Vec::new());
                 }
             }
-            ast::ItemType::Function(f) => {
+            ast::ItemType::Enum(e) => {
                 // Check the following:
-                // * Arguments must have unique names
-                // * Argument types must be sized
-                let mut visited_args = HashMap::new();
-                let mut these_dependencies = Vec::new();
+                // * The representation must be an integer
+                // * Variants must have unique names
+                // * Variants must have unique discriminants
+                // * Discriminants must fit in the representation
+                let range = match &e.repr {
+                    ast::Type::Primitive { primitive, .. } => integer_range(primitive),
+                    _ => None,
+                };
+                if range.is_none() {
+                    valid = false;
+                    context.report(
+                        &Diagnostic::error()
+                            .with_message("enum representation must be an integer type")
+                            .with_labels(vec![Label::primary(
+                                e.repr.location().file_id,
+                                e.repr.location().span.clone(),
+                            )]),
+                    );
+                }
 
-                for (name, ty) in &f.args {
-                    // Check that the field name is unique
-                    if let Some(existing_field) = visited_args.insert(&name.identifier, name) {
+                let mut visited_variants = HashMap::new();
+                let mut visited_values = HashMap::new();
+                for (variant, value) in e.variants.iter().zip(e.discriminants()) {
+                    // Check that the variant name is unique
+                    if let Some(existing_variant) =
+                        visited_variants.insert(&variant.name.identifier, &variant.name)
+                    {
+                        valid = false;
                         context.report(
                             &Diagnostic::error()
-                                .with_message("argument name must be unique")
+                                .with_message("variant names must be unique")
                                 .with_labels(vec![
                                     Label::primary(
-                                        name.location.file_id,
-                                        name.location.span.clone(),
+                                        variant.name.location.file_id,
+                                        variant.name.location.span.clone(),
                                     )
-                                    .with_message("duplicate argument name"),
+                                    .with_message("duplicate variant name"),
                                     Label::secondary(
-                                        existing_field.location.file_id,
-                                        existing_field.location.span.clone(),
+                                        existing_variant.location.file_id,
+                                        existing_variant.location.span.clone(),
                                     )
                                     .with_message("first used here"),
                                 ]),
                         );
                     }
 
-                    if !is_sized(&ty, &items) {
+                    // Check that the discriminant is unique
+                    if let Some(existing_variant) = visited_values.insert(value, variant) {
+                        valid = false;
                         context.report(
                             &Diagnostic::error()
-                                .with_message("argument is not a sized type")
+                                .with_message("discriminants must be unique")
                                 .with_labels(vec![
                                     Label::primary(
-                                        ty.location().file_id,
-                                        ty.location().span.clone(),
+                                        variant.location.file_id,
+                                        variant.location.span.clone(),
                                     )
-                                    .with_message("structs without fields are not sized, but can be referenced through pointers"),
+                                    .with_message(format!("has value {}", value)),
+                                    Label::secondary(
+                                        existing_variant.location.file_id,
+                                        existing_variant.location.span.clone(),
+                                    )
+                                    .with_message("first used here"),
                                 ]),
                         );
                     }
 
-                    add_layout_deps(&ty, &mut these_dependencies);
+                    if let Some(range) = &range
+                        && !range.contains(&value)
+                    {
+                        valid = false;
+                        context.report(
+                            &Diagnostic::error()
+                                .with_message(
+                                    "discriminant does not fit in the enum representation",
+                                )
+                                .with_labels(vec![
+                                    Label::primary(
+                                        variant.location.file_id,
+                                        variant.location.span.clone(),
+                                    )
+                                    .with_message(format!("has value {}", value)),
+                                    Label::secondary(
+                                        e.repr.location().file_id,
+                                        e.repr.location().span.clone(),
+                                    )
+                                    .with_message(format!(
+                                        "representation holds values {} to {}",
+                                        range.start(),
+                                        range.end()
+                                    )),
+                                ]),
+                        );
+                    }
                 }
 
-                if let Some(returns) = &f.returns {
-                    add_layout_deps(&returns, &mut these_dependencies);
+                dependencies.insert(name.clone(), Vec::new());
+            }
+            ast::ItemType::Flags(f) => {
+                // Check the following:
+                // * The representation must be an integer
+                // * Flags must have unique names
+                // * Values must fit in the representation
+                // Overlapping flags are checked when their values are evaluated.
+                let range = match &f.repr {
+                    ast::Type::Primitive { primitive, .. } => integer_range(primitive),
+                    _ => None,
+                };
+                if range.is_none() {
+                    valid = false;
+                    context.report(
+                        &Diagnostic::error()
+                            .with_message("flags representation must be an integer type")
+                            .with_labels(vec![Label::primary(
+                                f.repr.location().file_id,
+                                f.repr.location().span.clone(),
+                            )]),
+                    );
+                }
+
+                let mut visited_flags = HashMap::new();
+                for flag in &f.flags {
+                    if let Some(existing_flag) =
+                        visited_flags.insert(&flag.name.identifier, &flag.name)
+                    {
+                        valid = false;
+                        context.report(
+                            &Diagnostic::error()
+                                .with_message("flag names must be unique")
+                                .with_labels(vec![
+                                    Label::primary(
+                                        flag.name.location.file_id,
+                                        flag.name.location.span.clone(),
+                                    )
+                                    .with_message("duplicate flag name"),
+                                    Label::secondary(
+                                        existing_flag.location.file_id,
+                                        existing_flag.location.span.clone(),
+                                    )
+                                    .with_message("first used here"),
+                                ]),
+                        );
+                    }
 
-                    if !is_sized(&returns, &items) {
+                    let value = flag.value.value().expect("flags are folded");
+                    if let Some(range) = &range
+                        && !range.contains(&value)
+                    {
+                        valid = false;
                         context.report(
                             &Diagnostic::error()
-                                .with_message("return type is not a sized type")
+                                .with_message("flag does not fit in the flags representation")
                                 .with_labels(vec![
                                     Label::primary(
-                                        returns.location().file_id,
-                                        returns.location().span.clone(),
+                                        flag.location.file_id,
+                                        flag.location.span.clone(),
+                                    )
+                                    .with_message(format!("has value {}", bits(value))),
+                                    Label::secondary(
+                                        f.repr.location().file_id,
+                                        f.repr.location().span.clone(),
                                     )
-                                    .with_message("structs without fields are not sized, but can be referenced through pointers"),
+                                    .with_message(format!(
+                                        "representation holds values {} to {}",
+                                        range.start(),
+                                        range.end()
+                                    )),
                                 ]),
                         );
                     }
                 }
 
+                dependencies.insert(name.clone(), Vec::new());
+            }
+            ast::ItemType::Alias(a) => {
+                let mut these_dependencies = Vec::new();
+                add_layout_deps(&a.ty, &mut these_dependencies);
+                add_alias_deps(&a.ty, items, &mut these_dependencies);
+                dependencies.insert(name.clone(), these_dependencies);
+            }
+            ast::ItemType::Constant(c) => {
+                // Check the following:
+                // * The type must be an integer
+                // * The value must fit in the type
+                let range = match &c.ty {
+                    ast::Type::Primitive { primitive, .. } => integer_range(primitive),
+                    _ => None,
+                };
+                let value = c.value.value().expect("constants are folded");
+                if let Some(range) = range {
+                    if !range.contains(&value) {
+                        valid = false;
+                        context.report(
+                            &Diagnostic::error()
+                                .with_message("constant value does not fit in its type")
+                                .with_labels(vec![
+                                    Label::primary(c.location.file_id, c.location.span.clone())
+                                        .with_message(format!("has value {}", value)),
+                                    Label::secondary(
+                                        c.ty.location().file_id,
+                                        c.ty.location().span.clone(),
+                                    )
+                                    .with_message(format!(
+                                        "type holds values {} to {}",
+                                        range.start(),
+                                        range.end()
+                                    )),
+                                ]),
+                        );
+                    }
+                } else {
+                    valid = false;
+                    context.report(
+                        &Diagnostic::error()
+                            .with_message("constant type must be an integer type")
+                            .with_labels(vec![Label::primary(
+                                c.ty.location().file_id,
+                                c.ty.location().span.clone(),
+                            )]),
+                    );
+                }
+            }
+            ast::ItemType::Union(u) => {
+                // Check the following:
+                // * Variants must have unique names
+                // * Payload types must be sized
+                let mut visited_variants = HashMap::new();
+                let mut these_dependencies = Vec::new();
+                for variant in &u.variants {
+                    // Check that the variant name is unique
+                    if let Some(existing_variant) =
+                        visited_variants.insert(&variant.name.identifier, &variant.name)
+                    {
+                        valid = false;
+                        context.report(
+                            &Diagnostic::error()
+                                .with_message("variant names must be unique")
+                                .with_labels(vec![
+                                    Label::primary(
+                                        variant.name.location.file_id,
+                                        variant.name.location.span.clone(),
+                                    )
+                                    .with_message("duplicate variant name"),
+                                    Label::secondary(
+                                        existing_variant.location.file_id,
+                                        existing_variant.location.span.clone(),
+                                    )
+                                    .with_message("first used here"),
+                                ]),
+                        );
+                    }
+
+                    if let Some(ty) = &variant.ty {
+                        if !is_sized(ty, items) {
+                            valid = false;
+                            context.report(
+                                &Diagnostic::error()
+                                    .with_message("variant payload is not a sized type")
+                                    .with_labels(vec![
+                                        Label::primary(
+                                            ty.location().file_id,
+                                            ty.location().span.clone(),
+                                        )
+                                        .with_message(unsized_reason(ty, items)),
+                                    ]),
+                            );
+                        }
+
+                        add_layout_deps(ty, &mut these_dependencies);
+                        add_alias_deps(ty, items, &mut these_dependencies);
+                    }
+                }
+                dependencies.insert(name.clone(), these_dependencies);
+            }
+            ast::ItemType::Interface(i) => {
+                valid &= check_interface(context, items, i);
+                // The C++ adapter and Python callbacks need complete argument and return types
+                let mut these_dependencies = Vec::new();
+                for f in i.methods.iter().map(|m| &m.function) {
+                    for ty in f.args.iter().map(|arg| &arg.ty).chain(f.returns.as_deref()) {
+                        add_layout_deps(ty, &mut these_dependencies);
+                        add_alias_deps(ty, items, &mut these_dependencies);
+                    }
+                }
+                dependencies.insert(name.clone(), these_dependencies);
+            }
+            ast::ItemType::Function(f) => {
+                valid &= check_signature(context, items, f);
+                if let Some(method_of) = &f.method_of {
+                    valid &= check_method(context, items, f, method_of);
+                    if ast::find_attribute(&item.attributes, "destructor").is_some() {
+                        valid &= check_destructor(context, items, f, method_of);
+                    }
+                }
+                let mut these_dependencies = Vec::new();
+                for ty in f.args.iter().map(|arg| &arg.ty).chain(f.returns.as_deref()) {
+                    add_layout_deps(ty, &mut these_dependencies);
+                    add_alias_deps(ty, items, &mut these_dependencies);
+                }
                 dependencies.insert(name.clone(), these_dependencies);
             }
         }
     }
-    Ok(dependencies)
+
+    valid &= check_unique_destructors(context, items);
+
+    if valid { Ok(dependencies) } else { Err(()) }
 }
@@ -15,9 +15,9 @@ extern "C" {
 
 {% for f in items.functions %}
 {%- call m::docs("", f.docs) %}
-VELLUM_ABI {{ f.returns|retty }} {{ f.name }}(
+VELLUM_ABI {{ f.returns|retty }} {{ f.symbol() }}(
 {%- for arg in f.args %}
-  {{ arg.1|ty }} {{ arg.0 }}{% call m::comma() %}
+  {{ arg.ty|ty }} {{ arg.name }}{% call m::comma() %}
 {%- endfor %}
 ) noexcept;
 {% endfor %}
@@ -25,21 +25,82 @@ VELLUM_ABI {{ f.returns|retty }} {{ f.name }}(
 }
 }
 
+// Handles, with the functions of their `impl` blocks
+{% for i in items.impls() -%}
+{%- call m::open_namespace(i.package) %}
+struct {{ i.name }};
+{%- call m::close_namespace(i.package) %}
+{% endfor %}
+{% for i in items.impls() %}
+{%- call m::open_namespace(i.package) %}
+{%- if let Some(destructor) = i.destructor %}
+// Owns a pointer to `{{ i.ty|ty }}`, and calls the functions of `{{ i.ty|ty }}` as methods of it.
+struct {{ i.name }} {
+  vellum::owned<{{ i.ty|ty }} *> self;
+
+  // Take ownership of a pointer, which is released by `{{ destructor.method_name() }}`.
+  explicit {{ i.name }}({{ i.ty|ty }} *self) noexcept
+      : self(self, &::vellum_private_abi::{{ destructor.symbol() }}) {}
+  {{ i.name }}(vellum::owned<{{ i.ty|ty }} *> self) noexcept : self(std::move(self)) {}
+  {{ i.name }}(vellum::detail::abi::owned<{{ i.ty|ty }} *> self) noexcept : self(std::move(self)) {}
+  operator vellum::detail::abi::owned<{{ i.ty|ty }} *>() && noexcept { return std::move(self); }
+{%- else %}
+// Calls the functions of `{{ i.ty|ty }}` as methods of a pointer to it.
+struct {{ i.name }} {
+  {{ i.ty|ty }} *self;
+
+  explicit {{ i.name }}({{ i.ty|ty }} *self) noexcept : self(self) {}
+{%- endif %}
+{% for f in i.functions %}
+{%- call m::docs("  ", f.docs) %}
+  {{ f.attributes|attributes }}{% if !f.has_self() %}static {% endif %}{{ f.returns|retty_raii(items) }} {{ f.method_name() }}(
+{%- for arg in f.method_args() %}
+    {{ arg.ty|ty_raii(items) }} {{ arg.name }}{% call m::comma() %}
+{%- endfor %}
+  ){% if f.has_self() %} const{% endif %} noexcept;
+{% endfor -%}
+};
+{%- call m::close_namespace(i.package) %}
+{% endfor %}
+
 {% for f in items.functions %}
+{%- call m::open_namespace(f.package) %}
 {%- call m::docs("", f.docs) %}
-inline {{ f.returns|retty_raii }} {{ f.name }}(
+{{ f.attributes|attributes }}inline {{ f.returns|retty_raii(items) }} {{ f.name }}(
 {%- for arg in f.args %}
-  {{ arg.1|ty_raii }} {{ arg.0 }}{% call m::comma() %}
+  {{ arg.ty|ty_raii(items) }} {{ arg.name }}{% call m::comma() %}
 {%- endfor %}
 ) noexcept {
   {%- if f.returns.is_some() %}
-  return vellum_private_abi::{{ f.name }}(
+  return ::vellum_private_abi::{{ f.symbol() }}(
   {%- else %}
-  vellum_private_abi::{{ f.name }}(
+  ::vellum_private_abi::{{ f.symbol() }}(
   {%- endif %}
   {%- for arg in f.args %}
-    std::move({{ arg.0 }}){% call m::comma() %}
+    std::move({{ arg.name }}){% call m::comma() %}
   {%- endfor %}
   );
 }
+{%- call m::close_namespace(f.package) %}
 {% endfor %}
+
+{% for i in items.impls() %}
+{%- for f in i.functions %}
+{%- call m::open_namespace(i.package) %}
+inline {{ f.returns|retty_raii(items) }} {{ i.name }}::{{ f.method_name() }}(
+{%- for arg in f.method_args() %}
+  {{ arg.ty|ty_raii(items) }} {{ arg.name }}{% call m::comma() %}
+{%- endfor %}
+){% if f.has_self() %} const{% endif %} noexcept {
+  return ::vellum_private_abi::{{ f.symbol() }}(
+{%- if f.has_self() %}
+    self{% if i.destructor.is_some() %}.get(){% endif %}{% if !f.method_args().is_empty() %},{% endif %}
+{%- endif %}
+{%- for arg in f.method_args() %}
+    std::move({{ arg.name }}){% call m::comma() %}
+{%- endfor %}
+  );
+}
+{%- call m::close_namespace(i.package) %}
+{% endfor %}
+{%- endfor %}
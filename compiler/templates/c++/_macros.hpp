@@ -12,3 +12,34 @@
   {%- endif %}
 {%- endmacro %}
 
+
+{%- macro open_namespace(package) %}
+  {%- if !package.is_empty() %}
+namespace {{ package.join("::") }} {
+  {%- endif %}
+{%- endmacro %}
+
+{%- macro close_namespace(package) %}
+  {%- if !package.is_empty() %}
+} // namespace {{ package.join("::") }}
+  {%- endif %}
+{%- endmacro %}
+
+{#- Bitwise operators for a set of flags, which is an enum class #}
+{%- macro flags_operators(name, repr) %}
+constexpr {{ name }} operator|({{ name }} a, {{ name }} b) noexcept {
+  return static_cast<{{ name }}>(static_cast<{{ repr }}>(a) | static_cast<{{ repr }}>(b));
+}
+constexpr {{ name }} operator&({{ name }} a, {{ name }} b) noexcept {
+  return static_cast<{{ name }}>(static_cast<{{ repr }}>(a) & static_cast<{{ repr }}>(b));
+}
+constexpr {{ name }} operator^({{ name }} a, {{ name }} b) noexcept {
+  return static_cast<{{ name }}>(static_cast<{{ repr }}>(a) ^ static_cast<{{ repr }}>(b));
+}
+constexpr {{ name }} operator~({{ name }} a) noexcept {
+  return static_cast<{{ name }}>(~static_cast<{{ repr }}>(a));
+}
+constexpr {{ name }} &operator|=({{ name }} &a, {{ name }} b) noexcept { return a = a | b; }
+constexpr {{ name }} &operator&=({{ name }} &a, {{ name }} b) noexcept { return a = a & b; }
+constexpr {{ name }} &operator^=({{ name }} &a, {{ name }} b) noexcept { return a = a ^ b; }
+{%- endmacro %}
@@ -1,22 +1,199 @@
 {% import "c++/_macros.hpp" as m %}
 
+// Constants
+{% for c in items.constants -%}
+{%- call m::open_namespace(c.package) %}
+{%- call m::docs("", c.docs) %}
+inline constexpr {{ c.ty|ty }} {{ c.name }} = {{ c.value|int_literal }};
+{%- call m::close_namespace(c.package) %}
+{% endfor %}
+
+// Enumerations
+{% for e in items.enums %}
+{%- call m::open_namespace(e.package) %}
+{%- call m::docs("", e.docs) %}
+enum class {{ e.name }} : {{ e.repr|ty }} {
+{%- for v in e.variants %}
+{%- call m::docs("  ", v.docs) %}
+  {{ v.name }} = {{ v.value|int_literal }},
+{%- endfor %}
+};
+{%- if e.flags %}
+{%- call m::flags_operators(e.name, e.repr|ty) %}
+{%- endif %}
+{%- call m::close_namespace(e.package) %}
+
+{% endfor %}
+
 // Forward declarations, including incomplete types
 {% for s in items.abstract_structs -%}
+{%- call m::open_namespace(s.package) %}
 {%- call m::docs("", s.docs) %}
 struct {{ s.name }};
+{%- call m::close_namespace(s.package) %}
 {% endfor -%}
-{% for s in items.structs -%}
-struct {{ s.name }};
+{% for d in items.definitions -%}
+{%- if !d.is_alias() -%}
+{%- call m::open_namespace(d.package()) %}
+struct {{ d.name() }};
+{%- call m::close_namespace(d.package()) %}
+{% endif -%}
 {% endfor %}
 
 // Definitions of complete types
-{% for s in items.structs %}
+{% for d in items.definitions %}
+{%- call m::open_namespace(d.package()) %}
+{%- match d %}
+{%- when Definition::Struct with (s) %}
 {%- call m::docs("", s.docs) %}
-struct {{ s.name }} {
+struct {{ s.attributes|layout }}{{ s.name }}{% if let Some(base) = s.base %} : {{ base|ty }}{% endif %} {
+{%- if let Some(layout) = s.layout %}
+  alignas({{ layout.align }}) unsigned char _opaque[{{ layout.size }}];
+{%- endif %}
 {%- for field in s.fields %}
 {%- call m::docs("  ", field.docs) %}
-  {{ field.ty|ty }} {{ field.name }};
+  {% if loop.first && field.width.is_none() %}{% if let Some(base) = s.base %}alignas({{ base|ty }}) alignas({{ field.ty|ty }}) {% endif %}{% endif -%}
+  {{ field.attributes|attributes }}{{ field.ty|ty }} {{ field.name }}{% if let Some(width) = field.width %} : {{ width }}{% endif %};
+{%- endfor %}
+{%- if let Some(base) = s.base %}
+
+  // The C layout, with the base as the first member
+  struct {{ s.attributes|layout }}_c_layout {
+    {{ base|ty }} base;
+{%- for field in s.fields %}
+    {% if let Some(align) = field.attributes.align() %}alignas({{ align }}) {% endif %}{{ field.ty|ty }} {{ field.name }}{% if let Some(width) = field.width %} : {{ width }}{% endif %};
+{%- endfor %}
+  };
+{%- endif %}
+};
+{%- if let Some(base) = s.base %}
+{#- The base is laid out like the first member of a C struct: the root base is standard-layout, so
+    it's at the start, and the first field is aligned so that it follows all of the base rather
+    than its tail padding. #}
+{%- if !items.is_derived(base) %}
+static_assert(std::is_standard_layout_v<{{ base|ty }}>);
+{%- endif %}
+static_assert(std::is_trivially_copyable_v<{{ s.name }}>);
+static_assert(sizeof({{ s.name }}) == sizeof({{ s.name }}::_c_layout));
+static_assert(alignof({{ s.name }}) == alignof({{ s.name }}::_c_layout));
+{%- endif %}
+{%- when Definition::Union with (u) %}
+{%- call m::docs("", u.docs) %}
+struct {{ u.name }} {
+  enum class Tag : uint32_t {
+{%- for v in u.variants %}
+{%- call m::docs("    ", v.docs) %}
+    {{ v.name }} = {{ loop.index0 }},
+{%- endfor %}
+  };
+{%- if u.has_payload() %}
+
+  union Value {
+    Value() noexcept {}
+{%- for v in u.variants %}
+{%- if let Some(ty) = v.ty %}
+    {{ ty|ty }} {{ v.name }};
+{%- endif %}
+{%- endfor %}
+  };
+{%- endif %}
+
+  Tag tag;
+{%- if u.has_payload() %}
+  Value value;
+{%- endif %}
+{% for v in u.variants %}
+{%- if let Some(ty) = v.ty %}
+  static {{ u.name }} make_{{ v.name }}({{ ty|ty }} payload) noexcept {
+    {{ u.name }} result{};
+    result.tag = Tag::{{ v.name }};
+    result.value.{{ v.name }} = std::move(payload);
+    return result;
+  }
+  bool is_{{ v.name }}() const noexcept { return tag == Tag::{{ v.name }}; }
+  {{ ty|ty }} &get_{{ v.name }}() noexcept { return value.{{ v.name }}; }
+  const {{ ty|ty }} &get_{{ v.name }}() const noexcept { return value.{{ v.name }}; }
+{%- else %}
+  static {{ u.name }} make_{{ v.name }}() noexcept {
+    {{ u.name }} result{};
+    result.tag = Tag::{{ v.name }};
+    return result;
+  }
+  bool is_{{ v.name }}() const noexcept { return tag == Tag::{{ v.name }}; }
+{%- endif %}
+{% endfor %}
+  // Call `f` with the active payload, or `std::monostate` for variants without one.
+  template <typename F> decltype(auto) visit(F &&f) {
+    switch (tag) {
+{%- for v in u.variants %}
+    case Tag::{{ v.name }}:
+{%- if v.ty.is_some() %}
+      return std::forward<F>(f)(value.{{ v.name }});
+{%- else %} {
+      std::monostate empty;
+      return std::forward<F>(f)(empty);
+    }
+{%- endif %}
+{%- endfor %}
+    }
+    std::abort();
+  }
+
+  template <typename F> decltype(auto) visit(F &&f) const {
+    switch (tag) {
+{%- for v in u.variants %}
+    case Tag::{{ v.name }}:
+{%- if v.ty.is_some() %}
+      return std::forward<F>(f)(value.{{ v.name }});
+{%- else %} {
+      std::monostate empty;
+      return std::forward<F>(f)(empty);
+    }
+{%- endif %}
+{%- endfor %}
+    }
+    std::abort();
+  }
+};
+
+static_assert(std::is_standard_layout_v<{{ u.name }}>);
+static_assert(std::is_trivially_copyable_v<{{ u.name }}>);
+{%- when Definition::Interface with (i) %}
+{%- call m::docs("", i.docs) %}
+struct {{ i.name }} {
+{%- for method in i.methods %}
+{%- call m::docs("  ", method.docs) %}
+  {{ method.returns|retty }} (*{{ method.name }})(void *self{% for arg in method.args %}, {{ arg.ty|ty }} {{ arg.name }}{% endfor %});
+{%- endfor %}
+  void *self;
+
+  // An object implementing the interface, which can be passed with `from`.
+  struct Interface {
+    virtual ~Interface() = default;
+{%- for method in i.methods %}
+    virtual {{ method.returns|retty }} {{ method.name }}({% for arg in method.args %}{{ arg.ty|ty }} {{ arg.name }}{% if !loop.last %}, {% endif %}{% endfor %}) = 0;
 {%- endfor %}
+  };
+
+  // Borrow any object with these methods, which must outlive the interface.
+  template <typename Object> static {{ i.name }} from(Object &object) noexcept {
+    return {{ i.name }}{
+{%- for method in i.methods %}
+      [](void *self{% for arg in method.args %}, {{ arg.ty|ty }} {{ arg.name }}{% endfor %}) noexcept -> {{ method.returns|retty }} {
+        return static_cast<Object *>(self)->{{ method.name }}({% for arg in method.args %}{{ arg.name }}{% if !loop.last %}, {% endif %}{% endfor %});
+      },
+{%- endfor %}
+      static_cast<void *>(std::addressof(object)),
+    };
+  }
 };
 
+static_assert(std::is_standard_layout_v<{{ i.name }}>);
+static_assert(std::is_trivially_copyable_v<{{ i.name }}>);
+{%- when Definition::Alias with (a) %}
+{%- call m::docs("", a.docs) %}
+using {{ a.name }} = {{ a.ty|ty }};
+{%- endmatch %}
+{%- call m::close_namespace(d.package()) %}
+
 {% endfor %}
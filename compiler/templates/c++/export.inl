@@ -24,22 +24,22 @@ extern "C" {
 
 {% for f in items.functions %}
 {%- call m::docs("", f.docs) %}
-VELLUM_ABI {{ f.returns|retty }} {{ f.name }}(
+VELLUM_ABI {{ f.returns|retty }} {{ f.symbol() }}(
 {%- for arg in f.args %}
-  {{ arg.1|ty }} {{ arg.0 }}{% call m::comma() %}
+  {{ arg.ty|ty }} {{ arg.name }}{% call m::comma() %}
 {%- endfor %}
 ) noexcept {
 {%- if f.returns.is_some() %}
-  auto __vellum_result = ::vellum_export::{{ f.name }}(
+  auto __vellum_result = ::vellum_export::{{ f.name|qualified(f.package) }}(
 {%- for arg in f.args %}
-    std::move({{ arg.0 }}){% call m::comma() %}
+    std::move({{ arg.name }}){% call m::comma() %}
 {%- endfor %}
   );
   return std::move(__vellum_result);
 {%- else %}
-  ::vellum_export::{{ f.name }}(
+  ::vellum_export::{{ f.name|qualified(f.package) }}(
 {%- for arg in f.args %}
-    std::move({{ arg.0 }}){% call m::comma() %}
+    std::move({{ arg.name }}){% call m::comma() %}
 {%- endfor %}
   );
 {%- endif %}
@@ -4,10 +4,22 @@
 // -----------------------------------------------------------------------------
 
 #pragma once
+{%- if !items.docs.is_empty() %}
+
+/*! \file
+{%- for doc in items.docs %}
+ *{{ doc }}
+{%- endfor %}
+ */
+{%- endif %}
 
 #include <cstdint>
 #include <cstddef>
+#include <cstdlib>
+#include <memory>
+#include <type_traits>
 #include <utility>
+#include <variant>
 #include <vellum.hpp>
 
 {% include "c++/_structs.hpp" %}
@@ -25,7 +25,7 @@ extern "C" {
 {% for f in items.functions %}
 {{ f.returns|retty }} vellum_export_{{ f.name }}(
 {%- for arg in f.args %}
-    {{ arg.1|ty }} {{ arg.0 }}{% call m::comma() %}
+    {{ arg.ty|ty }} {{ arg.name }}{% call m::comma() %}
 {%- endfor %}
 );
 
@@ -34,7 +34,7 @@ extern "C" {
 {% for f in items.functions %}
 VELLUM_ABI {{ f.returns|retty }} {{ f.name }}(
 {%- for arg in f.args %}
-    {{ arg.1|ty }} {{ arg.0 }}{% call m::comma() %}
+    {{ arg.ty|ty }} {{ arg.name }}{% call m::comma() %}
 {%- endfor %}
 ) {
 {%- if f.returns.is_some() %}
@@ -43,7 +43,7 @@ VELLUM_ABI {{ f.returns|retty }} {{ f.name }}(
     vellum_export_{{ f.name }}(
 {%- endif %}
 {%- for arg in f.args %}
-        {{ arg.0 }}{% call m::comma() %}
+        {{ arg.name }}{% call m::comma() %}
 {%- endfor %}
     );
 }
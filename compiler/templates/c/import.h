@@ -4,6 +4,14 @@
 // -----------------------------------------------------------------------------
 
 #pragma once
+{%- if !items.docs.is_empty() %}
+
+/*! \file
+{%- for doc in items.docs %}
+ *{{ doc }}
+{%- endfor %}
+ */
+{%- endif %}
 
 #include <stdint.h>
 #include <stddef.h>
@@ -20,44 +28,160 @@
 #  endif
 #endif
 
+#ifndef VELLUM_NONNULL
+#  if defined(__clang__)
+#    define VELLUM_NONNULL _Nonnull
+#    define VELLUM_NULLABLE _Nullable
+#  else
+#    define VELLUM_NONNULL
+#    define VELLUM_NULLABLE
+#  endif
+#endif
+
+#ifndef VELLUM_INT128
+#  if defined(__SIZEOF_INT128__)
+#    define VELLUM_INT128
+__extension__ typedef __int128 vellum_int128_t;
+__extension__ typedef unsigned __int128 vellum_uint128_t;
+#  endif
+#endif
+
+#if defined(__clang__)
+#  pragma clang diagnostic push
+#  pragma clang diagnostic ignored "-Wnullability-completeness"
+#  pragma clang diagnostic ignored "-Wnullability-extension"
+#endif
+
 {% import "c/_macros.h" as m %}
 
+{#- Concrete slice, owned, shared, optional and closure typedefs (C has no templates).
+    Owned slice deleters take the fat pointer by value. #}
+{% macro type_decls(decls) %}
+{%- for d in decls %}
+{%- match d %}
+{%- when TypeDecl::Slice with (d) %}
+typedef struct { {{ d.data_decl }}; size_t len; } vellum_slice_{{ d.name }};
+{%- when TypeDecl::OwnedPtr with (d) %}
+typedef struct { {{ d.data_c_type }} data; void (*deleter)({{ d.data_c_type }}); } vellum_owned_ptr_{{ d.name }};
+{%- when TypeDecl::OwnedSlice with (d) %}
+typedef struct { {{ d.slice_name }} slice_data; void (*deleter)({{ d.slice_name }}); } vellum_owned_slice_{{ d.name }};
+{%- when TypeDecl::SharedPtr with (d) %}
+typedef struct { {{ d.data_c_type }} data; void (*retain)({{ d.data_c_type }}); void (*release)({{ d.data_c_type }}); } vellum_shared_ptr_{{ d.name }};
+{%- when TypeDecl::Closure with (d) %}
+typedef struct { {{ d.call_decl }}; void *state; void (*deleter)(void *); } vellum_closure_{{ d.name }};
+{%- when TypeDecl::BorrowedClosure with (d) %}
+typedef struct { {{ d.call_decl }}; void *state; } vellum_borrowed_closure_{{ d.name }};
+{%- when TypeDecl::Optional with (d) %}
+typedef struct { bool present; {{ d.value_decl }}; } vellum_optional_{{ d.name }};
+{%- endmatch %}
+{%- endfor %}
+{%- endmacro %}
+
+{% for c in items.constants -%}
+{%- call m::docs("", c.docs) %}
+#define {{ c.name }} (({{ c.ty|ty }}){{ c.value|int_literal }})
+{% endfor %}
 {% for s in items.abstract_structs -%}
 {%- call m::docs("", s.docs) %}
-struct {{ s.name }};
+typedef struct {{ s.name }} {{ s.name }};
+{% endfor %}
+{% for d in items.definitions -%}
+{%- if !d.is_alias() -%}
+typedef struct {{ d.name() }} {{ d.name() }};
+{% endif -%}
+{% endfor %}
+
+{% for e in items.enums %}
+{%- call m::docs("", e.docs) %}
+typedef {{ e.repr|ty }} {{ e.name }};
+{%- for v in e.variants %}
+{%- call m::docs("", v.docs) %}
+#define {{ e.name }}_{{ v.name }} (({{ e.name }}){{ v.value|int_literal }})
+{%- endfor %}
+
 {% endfor %}
 
-{% for s in items.structs %}
+{% for d in items.definitions %}
+{%- call type_decls(preludes[loop.index0]) %}
+{%- match d %}
+{%- when Definition::Struct with (s) %}
 {%- call m::docs("", s.docs) %}
 struct {{ s.name }} {
+{%- if let Some(layout) = s.layout %}
+    _Alignas({{ layout.align }}) unsigned char _opaque[{{ layout.size }}];
+{%- endif %}
+{%- if let Some(base) = s.base %}
+    {{ base|decl("base") }};
+{%- endif %}
 {%- for field in s.fields %}
 {%- call m::docs("    ", field.docs) %}
-    {{ field.ty|ty }} {{ field.name }};
+    {{ field.ty|decl(field.name) }}{% if let Some(width) = field.width %} : {{ width }}{% endif %}{{ field.attributes|attributes }};
+{%- endfor %}
+}{{ s.attributes|layout }};
+{%- when Definition::Union with (u) %}
+typedef uint32_t {{ u.name }}_tag;
+{%- for v in u.variants %}
+{%- call m::docs("", v.docs) %}
+#define {{ u.name }}_tag_{{ v.name }} (({{ u.name }}_tag){{ loop.index0 }})
+{%- endfor %}
+{% if u.has_payload() %}
+typedef union {{ u.name }}_value {
+{%- for v in u.variants %}
+{%- if let Some(ty) = v.ty %}
+    {{ ty|decl(v.name) }};
+{%- endif %}
 {%- endfor %}
+} {{ u.name }}_value;
+{% endif %}
+{%- call m::docs("", u.docs) %}
+struct {{ u.name }} {
+    {{ u.name }}_tag tag;
+{%- if u.has_payload() %}
+    {{ u.name }}_value value;
+{%- endif %}
 };
+{%- when Definition::Interface with (i) %}
+{%- call m::docs("", i.docs) %}
+struct {{ i.name }} {
+{%- for method in i.methods %}
+{%- call m::docs("    ", method.docs) %}
+    {{ method|method_decl }};
+{%- endfor %}
+    void *self;
+};
+{%- when Definition::Alias with (a) %}
+{%- call m::docs("", a.docs) %}
+typedef {{ a.ty|decl(a.name) }};
+{%- endmatch %}
 
 {% endfor %}
+{%- call type_decls(function_type_decls) %}
 
-/* Concrete slice typedefs (C has no templates) */
-{% for d in slice_decls %}
-typedef struct { {{ d.elem_c_type }} * data; size_t len; } vellum_slice_{{ d.name }};
-{% endfor %}
-
-/* Concrete owned pointer typedefs */
-{% for d in owned_ptr_decls %}
-typedef struct { {{ d.data_c_type }} data; void (*deleter)({{ d.data_c_type }}); } vellum_owned_ptr_{{ d.name }};
-{% endfor %}
-
-/* Concrete owned slice typedefs (deleter takes fat pointer by value) */
-{% for d in owned_slice_decls %}
-typedef struct { {{ d.slice_name }} slice_data; void (*deleter)({{ d.slice_name }}); } vellum_owned_slice_{{ d.name }};
-{% endfor %}
+{#- Call helpers for interfaces, which pass the object to the method.
+    These follow all definitions, since arguments may be any complete type. #}
+{%- for d in items.definitions %}
+{%- match d %}
+{%- when Definition::Interface with (i) %}
+{%- for method in i.methods %}
+static inline {{ method.returns|retty }} {{ i.name }}_{{ method.name }}({{ i.name }} self
+{%- for arg in method.args %}, {{ arg.ty|decl(arg.name) }}{% endfor %}) {
+    {% if method.returns.is_some() %}return {% endif %}self.{{ method.name }}(self.self
+    {%- for arg in method.args %}, {{ arg.name }}{% endfor %});
+}
+{%- endfor %}
+{% else %}
+{%- endmatch %}
+{%- endfor %}
 
 {% for f in items.functions %}
 {%- call m::docs("", f.docs) %}
 VELLUM_ABI {{ f.returns|retty }} {{ f.name }}(
 {%- for arg in f.args %}
-    {{ arg.1|ty }} {{ arg.0 }}{% call m::comma() %}
+    {{ arg.ty|ty }} {{ arg.name }}{% call m::comma() %}
 {%- endfor %}
-) ;
+){{ f.attributes|attributes }};
 {% endfor %}
+
+#if defined(__clang__)
+#  pragma clang diagnostic pop
+#endif
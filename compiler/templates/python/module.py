@@ -4,7 +4,48 @@ Do not edit.
 """
 
 import ctypes as ct
+import enum
 import vellum
+{%- if !items.docs.is_empty() %}
+
+__doc__ = "\n".join([
+    __doc__,
+{%- for doc in items.docs %}
+    {{ doc|repr }},
+{%- endfor %}
+])
+{%- endif %}
+
+{%- for c in items.constants %}
+{%- for doc in c.docs %}
+#{{ doc }}
+{%- endfor %}
+{{ c.name }} = {{ c.value }}
+{%- endfor %}
+
+{%- for e in items.enums %}
+class {{ e.name }}({% if e.flags %}enum.IntFlag{% else %}enum.IntEnum{% endif %}):
+    __doc__ = "\n".join([
+    {%- for doc in e.docs %}
+        {{ doc|repr }},
+    {%- endfor %}
+    {%- set variant_docs = e | variant_docs %}
+    {%- if !variant_docs.is_empty() %}
+        {%- if !e.docs.is_empty() %}
+        "",
+        {%- endif %}
+        {%- for line in variant_docs %}
+        {{ line|repr }},
+        {%- endfor %}
+    {%- endif %}
+    ])
+    {%- for v in e.variants %}
+    {{ v.name }} = {{ v.value }}
+    {%- endfor %}
+
+{{ e.name }}._ctype_ = {{ e.repr|ty(items) }}
+
+{%- endfor %}
 
 {%- for s in items.abstract_structs %}
 class {{ s.name }}(ct.Structure):
@@ -17,8 +58,10 @@ class {{ s.name }}(ct.Structure):
 
 {%- endfor %}
 
-{%- for s in items.structs %}
-class {{ s.name }}(ct.Structure):
+{%- for d in items.definitions %}
+{%- match d %}
+{%- when Definition::Struct with (s) %}
+class {{ s.name }}({% if let Some(base) = s.base %}{{ base|ty(items) }}{% else %}ct.Structure{% endif %}):
     __doc__ = "\n".join([
     {%- for doc in s.docs %}
         {{ doc|repr }},
@@ -33,11 +76,120 @@ class {{ s.name }}(ct.Structure):
         {%- endfor %}
     {%- endif %}
     ])
+    {%- if let Some(layout) = s.layout %}
+
+vellum.layout({{ s.name }}, [
+    ('_opaque', ct.c_ubyte * {{ layout.size }}, None),
+], align={{ layout.align }})
+    {%- else if s|struct_uses_layout(items) %}
+
+vellum.layout({{ s.name }}, [
+{%- for field in s.fields %}
+    ('{{ field.name }}', {{ field.ty|ty(items) }}, {{ field.attributes.align()|optional_int }}),
+{%- endfor %}
+], packed={{ s.attributes.packed()|capitalize }}, align={{ s.attributes.align()|optional_int }})
+    {%- else %}
+    {%- if s.attributes.packed() %}
+    _layout_ = 'ms'
+    _pack_ = 1
+    {%- endif %}
     _fields_ = [
     {%- for field in s.fields %}
-        ('{{ field.name }}', {{ field.ty|ty }}),
+        ('{{ field.name }}', {{ field.ty|ty(items) }}{% if let Some(width) = field.width %}, {{ width }}{% endif %}),
     {%- endfor %}
     ]
+    {%- endif %}
+{%- when Definition::Union with (u) %}
+class {{ u.name }}(ct.Structure):
+    __doc__ = "\n".join([
+    {%- for doc in u.docs %}
+        {{ doc|repr }},
+    {%- endfor %}
+    {%- set variant_docs = u.variants | union_variant_docs %}
+    {%- if !variant_docs.is_empty() %}
+        {%- if !u.docs.is_empty() %}
+        "",
+        {%- endif %}
+        {%- for line in variant_docs %}
+        {{ line|repr }},
+        {%- endfor %}
+    {%- endif %}
+    ])
+
+    class Tag(enum.IntEnum):
+    {%- for v in u.variants %}
+        {{ v.name }} = {{ loop.index0 }}
+    {%- endfor %}
+    {%- if u.has_payload() %}
+
+    class _Value(ct.Union):
+        _fields_ = [
+        {%- for v in u.variants %}
+        {%- if let Some(ty) = v.ty %}
+            ('{{ v.name }}', {{ ty|ty(items) }}),
+        {%- endif %}
+        {%- endfor %}
+        ]
+    {%- endif %}
+
+    {%- if !(u|union_uses_layout(items)) %}
+    _fields_ = [
+        ('tag', ct.c_uint32),
+    {%- if u.has_payload() %}
+        ('_value', _Value),
+    {%- endif %}
+    ]
+    {%- endif %}
+    _payloads_ = [
+    {%- for v in u.variants %}
+    {%- if v.ty.is_some() %}
+        '{{ v.name }}',
+    {%- else %}
+        None,
+    {%- endif %}
+    {%- endfor %}
+    ]
+
+    @property
+    def value(self):
+        """The payload of the active variant, or None if it has no payload."""
+        name = self._payloads_[self.tag]
+        return None if name is None else getattr(self._value, name)
+    {%- if u|union_uses_layout(items) %}
+
+vellum.layout({{ u.name }}, [
+    ('tag', ct.c_uint32, None),
+    ('_value', {{ u.name }}._Value, None),
+])
+    {%- endif %}
+{%- when Definition::Interface with (i) %}
+class {{ i.name }}(vellum.Interface):
+    __doc__ = "\n".join([
+    {%- for doc in i.docs %}
+        {{ doc|repr }},
+    {%- endfor %}
+    {%- set method_docs = i.methods | method_docs %}
+    {%- if !method_docs.is_empty() %}
+        {%- if !i.docs.is_empty() %}
+        "",
+        {%- endif %}
+        {%- for line in method_docs %}
+        {{ line|repr }},
+        {%- endfor %}
+    {%- endif %}
+    ])
+    _fields_ = [
+    {%- for method in i.methods %}
+        ('{{ method.name }}', ct.CFUNCTYPE({{ method.returns|retty(items) }}, ct.c_void_p{% for arg in method.args %}, {{ arg.ty|ty(items) }}{% endfor %})),
+    {%- endfor %}
+        ('self', ct.c_void_p),
+    ]
+{%- when Definition::Alias with (a) %}
+{%- for doc in a.docs %}
+#{{ doc }}
+{%- endfor %}
+{{ a.name }} = {{ a.ty|ty(items) }}
+{%- endmatch %}
 
 {%- endfor %}
 
@@ -47,10 +199,17 @@ def load(*args):
     {%- for f in items.functions %}
     lib.{{ f.name }}.argtypes = [
         {%- for arg in f.args %}
-        {{ arg.1|ty }},
+        {{ arg.ty|ty(items) }},
         {%- endfor %}
     ]
-    lib.{{ f.name }}.restype = {{ f.returns|retty }}
+    lib.{{ f.name }}.restype = {{ f.returns|retty(items) }}
+    {%- set non_null = f.args|non_null_args %}
+    {%- if !non_null.is_empty() %}
+    lib.{{ f.name }} = vellum.non_null(lib.{{ f.name }}, '{{ f.name }}', {{ non_null }})
+    {%- endif %}
+    {%- if let Some(message) = f.attributes.deprecated() %}
+    lib.{{ f.name }} = vellum.deprecated(lib.{{ f.name }}, '{{ f.name }}', {{ message|optional_repr }})
+    {%- endif %}
     {%- endfor %}
 
     return lib
@@ -61,3 +220,79 @@ load.__doc__ = "\n".join([
     {{ line|repr }},
 {%- endfor %}
 ])
+
+{%- for i in items.impls() %}
+
+class {{ i.name }}:
+{%- if let Some(destructor) = i.destructor %}
+    """Owns a pointer to `{{ i.ty|ty(items) }}`, and calls the functions of `{{ i.ty|ty(items) }}` as methods of it."""
+
+    def __init__(self, lib, pointer):
+        """Take ownership of a pointer, released by `{{ destructor.method_name() }}`, or an owned pointer."""
+        self._lib = lib
+        self._owned = None
+        if hasattr(pointer, 'free'):
+            self._owned = pointer
+            pointer = pointer.data
+        self._as_parameter_ = pointer
+
+    def close(self):
+        """Release the pointer, unless it's already released."""
+        if self._owned is not None:
+            self._owned.free()
+        elif self._as_parameter_:
+            self._lib.{{ destructor.name }}(self._as_parameter_)
+        self._owned = None
+        self._as_parameter_ = None
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def __del__(self):
+        try:
+            self.close()
+        except Exception:
+            pass
+{%- else %}
+    """Calls the functions of `{{ i.ty|ty(items) }}` as methods of a pointer to it."""
+
+    def __init__(self, lib, pointer):
+        self._lib = lib
+        self._as_parameter_ = pointer
+{%- endif %}
+    {%- for f in i.functions %}
+    {%- if f.has_self() %}
+
+    def {{ f.method_name() }}(self{% for arg in f.method_args() %}, {{ arg.name }}{% endfor %}):
+        result = self._lib.{{ f.name }}(self._as_parameter_{% for arg in f.method_args() %}, {{ arg.name }}{% endfor %})
+        {%- if let Some(handle) = f.returns|owning_handle(items) %}
+        return {{ handle }}(self._lib, result)
+        {%- else %}
+        return result
+        {%- endif %}
+    {%- else %}
+
+    @staticmethod
+    def {{ f.method_name() }}(lib{% for arg in f.method_args() %}, {{ arg.name }}{% endfor %}):
+        result = lib.{{ f.name }}({% for arg in f.method_args() %}{{ arg.name }}{% if !loop.last %}, {% endif %}{% endfor %})
+        {%- if let Some(handle) = f.returns|owning_handle(items) %}
+        return {{ handle }}(lib, result)
+        {%- else %}
+        return result
+        {%- endif %}
+    {%- endif %}
+    {%- endfor %}
+{%- for f in i.functions %}
+{%- if !f.docs.is_empty() %}
+
+{{ i.name }}.{{ f.method_name() }}.__doc__ = "\n".join([
+{%- for doc in f.docs %}
+    {{ doc|repr }},
+{%- endfor %}
+])
+{%- endif %}
+{%- endfor %}
+{%- endfor %}
@@ -0,0 +1,942 @@
+//! Runs the compiler on small ABIs, checking the generated code and diagnostics.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Compile `source` for `language`, returning the output directory or the diagnostics.
+fn compile(test: &str, language: &str, source: &str) -> Result<PathBuf, String> {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join("compile")
+        .join(format!("{}_{}", test, language));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let abi = dir.join(format!("{}.abi", test));
+    fs::write(&abi, source).unwrap();
+    compile_file(language, &abi, &dir)
+}
+
+fn compile_file(language: &str, abi: &Path, dir: &Path) -> Result<PathBuf, String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_vellum"))
+        .env("NO_COLOR", "1")
+        .arg("compile")
+        .arg(language)
+        .arg(abi)
+        .arg("-o")
+        .arg(dir)
+        .output()
+        .unwrap();
+    if output.status.success() {
+        Ok(dir.to_path_buf())
+    } else {
+        Err(String::from_utf8(output.stderr).unwrap())
+    }
+}
+
+fn read(dir: &Path, file: &str) -> String {
+    fs::read_to_string(dir.join(file)).unwrap()
+}
+
+/// Run a command, failing with its output if it fails.
+fn run(command: &mut Command) {
+    let output = command.output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Compile `source` to C, and check that the header compiles.
+fn check_c(test: &str, source: &str) -> String {
+    let dir = compile(test, "c", source).unwrap();
+    fs::write(dir.join("main.c"), format!("#include \"{}.h\"\n", test)).unwrap();
+    run(Command::new("cc").current_dir(&dir).args([
+        "-std=c11",
+        "-Wall",
+        "-Werror",
+        "-fsyntax-only",
+        "main.c",
+    ]));
+    read(&dir, &format!("{}.h", test))
+}
+
+/// Compile `source` to C++, and check that the header compiles.
+fn check_cpp(test: &str, source: &str) -> String {
+    let dir = compile(test, "cpp", source).unwrap();
+    fs::write(dir.join("main.cpp"), format!("#include \"{}.hpp\"\n", test)).unwrap();
+    run(Command::new("c++")
+        .current_dir(&dir)
+        .args(["-std=c++17", "-Wall", "-Werror", "-fsyntax-only", "-I"])
+        .arg(library().join("c++/include"))
+        .arg("main.cpp"));
+    read(&dir, &format!("{}.hpp", test))
+}
+
+/// Compile `source` to Python, and check that `module` imports.
+fn check_python(test: &str, source: &str, module: &str) -> String {
+    let dir = compile(test, "python", source).unwrap();
+    run(Command::new("python3")
+        .current_dir(&dir)
+        .env("PYTHONPATH", library().join("python"))
+        .args(["-c", &format!("import {}", module)]));
+    read(&dir, &format!("{}.py", module.replace('.', "/")))
+}
+
+fn library() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../library")
+}
+
+/// The C header, C++ header and Python module generated from an ABI.
+struct Generated {
+    c: String,
+    cpp: String,
+    python: String,
+}
+
+/// Compile `source` to every language, checking each output.
+fn generate(test: &str, source: &str) -> Generated {
+    Generated {
+        c: check_c(test, source),
+        cpp: check_cpp(test, source),
+        python: check_python(test, source, test),
+    }
+}
+
+#[test]
+fn floats() {
+    let source = "struct Point { x: f32, y: f64 }\n\
+        function scale(point: Point, factor: f64) -> f32;\n";
+    let generated = generate("floats", source);
+    assert!(generated.c.contains("float x;"));
+    assert!(generated.c.contains("double y;"));
+    assert!(generated.cpp.contains("double factor"));
+    assert!(generated.python.contains("('x', ct.c_float)"));
+    assert!(generated.python.contains("('y', ct.c_double)"));
+}
+
+#[test]
+fn enums() {
+    let source = "enum Color: u8 { Red = 1, Green, Blue = 10 }\n\
+        function paint(color: Color) -> Color;\n";
+    let generated = generate("enums", source);
+    assert!(generated.c.contains("typedef uint8_t Color;"));
+    assert!(generated.c.contains("#define Color_Green ((Color)2)"));
+    assert!(generated.cpp.contains("enum class Color : uint8_t"));
+    assert!(generated.python.contains("Blue = 10"));
+}
+
+#[test]
+fn enum_variant_out_of_range() {
+    let source = "enum Color: u8 { Red = 255, Green }\n";
+    let error = compile("enum_variant_out_of_range", "c", source).unwrap_err();
+    assert!(error.contains("has value 256"), "{}", error);
+}
+
+#[test]
+fn duplicate_enum_discriminant() {
+    let source = "enum Color: u8 { Red = 2, Green = 1, Blue }\n";
+    let error = compile("duplicate_enum_discriminant", "c", source).unwrap_err();
+    assert!(error.contains("discriminants must be unique"), "{}", error);
+}
+
+#[test]
+fn unions() {
+    let source = "struct Circle { radius: f32 }\n\
+        union Shape { circle: Circle, square: f32, none }\n\
+        function area(shape: Shape) -> f32;\n";
+    let generated = generate("unions", source);
+    assert!(
+        generated
+            .c
+            .contains("#define Shape_tag_none ((Shape_tag)2)")
+    );
+    assert!(generated.c.contains("Circle circle;"));
+    assert!(
+        generated
+            .cpp
+            .contains("static Shape make_square(float payload)")
+    );
+    assert!(generated.python.contains("'circle',"));
+}
+
+#[test]
+fn union_as_name() {
+    let source = "struct S { union: u32 }\nunion U { union: S }\n";
+    let generated = generate("union_as_name", source);
+    assert!(generated.c.contains("uint32_t union_;"));
+}
+
+#[test]
+fn constants() {
+    let source = "const LEN: usize = 4;\n\
+        struct Buffer { data: [u8; LEN] }\n";
+    let generated = generate("constants", source);
+    assert!(generated.c.contains("#define LEN ((size_t)4)"));
+    assert!(generated.cpp.contains("LEN = 4"));
+    assert!(generated.python.contains("LEN = 4"));
+}
+
+#[test]
+fn unknown_constant() {
+    let source = "struct Buffer { data: [u8; LEN] }\n";
+    let error = compile("unknown_constant", "c", source).unwrap_err();
+    assert!(error.contains("no constant `LEN` found"), "{}", error);
+}
+
+#[test]
+fn aliases() {
+    let source = "type Handle = u64;\n\
+        type Callback = function(handle: Handle) -> u32;\n\
+        type Handler = closure(handle: Handle) -> void;\n\
+        function subscribe(handle: Handle, callback: Callback, handler: Handler) -> void;\n";
+    let generated = generate("aliases", source);
+    assert!(generated.c.contains("typedef uint64_t Handle;"));
+    assert!(generated.c.contains("Handle handle"));
+    assert!(
+        generated
+            .c
+            .contains("typedef vellum_closure_void_args_Handle Handler;")
+    );
+    assert!(generated.cpp.contains("using Handle = uint64_t;"));
+    assert!(generated.python.contains("Handle = ct.c_uint64"));
+}
+
+#[test]
+fn alias_cycle() {
+    let source = "type A = B;\ntype B = A;\n";
+    let error = compile("alias_cycle", "c", source).unwrap_err();
+    assert!(error.contains("refers to itself"), "{}", error);
+}
+
+#[test]
+fn type_as_name() {
+    let source = "struct S { type: u32 }\ntype T = S;\nfunction f(type: T) -> void;\n";
+    let generated = generate("type_as_name", source);
+    assert!(generated.python.contains("('type', ct.c_uint32)"));
+}
+
+#[test]
+fn packages() {
+    let source = "package acme.storage;\n\
+        struct Store;\n\
+        function create() -> mut * Store;\n";
+    let c = check_c("packages", source);
+    assert!(c.contains("acme_storage_Store"));
+    assert!(c.contains("acme_storage_create("));
+    let cpp = check_cpp("packages", source);
+    assert!(cpp.contains("namespace acme::storage {"));
+    let python = check_python("packages", source, "acme.storage");
+    assert!(python.contains("lib.acme_storage_create"));
+}
+
+#[test]
+fn package_as_name() {
+    let source = "struct S { package: u32 }\nfunction f(package: S) -> void;\n";
+    let generated = generate("package_as_name", source);
+    assert!(generated.cpp.contains("uint32_t package;"));
+}
+
+#[test]
+fn attributes() {
+    let source = "#[deprecated]\n\
+        struct S { #[deprecated(\"use b\")] a: u32, b: u32 }\n\
+        function f(#[deprecated] x: u32) -> void;\n";
+    generate("attributes", source);
+}
+
+#[test]
+fn unknown_attribute() {
+    let source = "#[frobnicate]\nstruct S { a: u32 }\n";
+    let error = compile("unknown_attribute", "c", source).unwrap_err();
+    assert!(
+        error.contains("unknown attribute `frobnicate`"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn deprecation() {
+    let source = "#[deprecated(\"use paint_v2\")]\n\
+        function paint(#[deprecated] color: u32) -> void;\n";
+    let generated = generate("deprecation", source);
+    assert!(
+        generated
+            .c
+            .contains("__attribute__((deprecated(\"use paint_v2\")))")
+    );
+    assert!(generated.c.contains("Argument `color` is deprecated."));
+    assert!(generated.cpp.contains("[[deprecated(\"use paint_v2\")]]"));
+    assert!(
+        generated
+            .python
+            .contains("vellum.deprecated(lib.paint, 'paint', \"use paint_v2\")")
+    );
+}
+
+#[test]
+fn nullable_pointers() {
+    let source = "function find(key: const ? u8, out: mut * u8) -> mut ? u8;\n";
+    let generated = generate("nullable_pointers", source);
+    assert!(generated.c.contains("uint8_t const * VELLUM_NULLABLE key"));
+    assert!(generated.c.contains("uint8_t * VELLUM_NONNULL out"));
+    assert!(generated.cpp.contains("vellum::not_null<uint8_t *> out"));
+    assert!(
+        generated
+            .python
+            .contains("vellum.non_null(lib.find, 'find', [(1, 'out')])")
+    );
+}
+
+#[test]
+fn void_pointers() {
+    let source = "function alloc(size: usize) -> mut ? void;\n\
+        function release(data: mut * void) -> void;\n";
+    let generated = generate("void_pointers", source);
+    assert!(generated.c.contains("void * VELLUM_NULLABLE alloc("));
+    assert!(generated.cpp.contains("vellum::not_null<void *> data"));
+    assert!(generated.python.contains("lib.alloc.restype = ct.c_void_p"));
+}
+
+#[test]
+fn void_value() {
+    let source = "struct S { v: void }\n";
+    compile("void_value", "c", source).unwrap_err();
+}
+
+#[test]
+fn comments() {
+    let source = "//! Bindings for a store.\n\
+        \n\
+        // A line comment\n\
+        /* A block\n   comment */\n\
+        struct S {\n\
+            /// The value\n\
+            a: u32, // trailing\n\
+        }\n\
+        \n\
+        /// Opens a store.\n\
+        function open(\n\
+            /// The path to the store.\n\
+            path: const string,\n\
+        ) -> S;\n";
+    let generated = generate("comments", source);
+    for output in [&generated.c, &generated.cpp, &generated.python] {
+        assert!(output.contains("Bindings for a store."));
+        assert!(output.contains("The path to the store."));
+        assert!(!output.contains("comment"));
+    }
+}
+
+#[test]
+fn escaped_keywords() {
+    let source = "struct K { class: u32, def: u32, _reserved0: u8 }\n";
+    let generated = generate("escaped_keywords", source);
+    assert!(generated.c.contains("uint32_t class;"));
+    assert!(generated.cpp.contains("class_;"));
+    assert!(generated.python.contains("'class_'"));
+    assert!(generated.python.contains("'def_'"));
+}
+
+#[test]
+fn integer_expressions() {
+    let source = "const PAGE: usize = 0x1000;\n\
+        const MODE: u16 = 0o755;\n\
+        const MASK: u32 = 0b1010 | 1 << 8;\n\
+        const BUFFER: usize = (PAGE - 1_000) * 2;\n\
+        struct Page { data: [u8; BUFFER / 64] }\n";
+    let generated = generate("integer_expressions", source);
+    assert!(generated.c.contains("uint8_t data[96];"));
+    assert!(generated.python.contains("MODE = 493"));
+    assert!(generated.python.contains("MASK = 266"));
+}
+
+#[test]
+fn division_by_zero() {
+    let source = "const A: u32 = 1 / (2 - 2);\n";
+    let error = compile("division_by_zero", "c", source).unwrap_err();
+    assert!(error.contains("division by zero"), "{}", error);
+}
+
+#[test]
+fn packed_and_aligned() {
+    let source = "#[packed]\n\
+        struct Header { tag: u8, len: u32 }\n\
+        #[align(16)]\n\
+        struct Vec4 { x: f32, y: f32, z: f32, #[align(8)] w: f32 }\n";
+    let generated = generate("packed_and_aligned", source);
+    assert!(generated.c.contains("} __attribute__((packed));"));
+    assert!(generated.c.contains("float w __attribute__((aligned(8)));"));
+    assert!(generated.python.contains("_pack_ = 1"));
+    assert!(generated.python.contains("align=16"));
+}
+
+#[test]
+fn alignment_not_power_of_two() {
+    let source = "#[align(12)]\nstruct S { a: u32 }\n";
+    let error = compile("alignment_not_power_of_two", "c", source).unwrap_err();
+    assert!(
+        error.contains("alignment must be a power of two"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn sized_opaque_structs() {
+    let source = "struct Mutex [size = 40, align = 8];\n\
+        struct Guarded { lock: Mutex, value: u32 }\n\
+        function lock(mutex: mut * Mutex) -> void;\n";
+    let generated = generate("sized_opaque_structs", source);
+    assert!(
+        generated
+            .c
+            .contains("_Alignas(8) unsigned char _opaque[40];")
+    );
+    assert!(
+        generated
+            .python
+            .contains("('_opaque', ct.c_ubyte * 40, None)")
+    );
+}
+
+#[test]
+fn opaque_size_not_multiple_of_alignment() {
+    let source = "struct Mutex [size = 10, align = 4];\n";
+    let error = compile("opaque_size_not_multiple_of_alignment", "c", source).unwrap_err();
+    assert!(
+        error.contains("size must be a multiple of the alignment"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn wide_and_platform_integers() {
+    let source = "struct Wide { a: i128, b: u128, c: c_char, d: c_int, e: c_long, f: c_ulong }\n\
+        const BIG: u128 = 1 << 100;\n";
+    let generated = generate("wide_and_platform_integers", source);
+    assert!(generated.c.contains("vellum_int128_t a;"));
+    assert!(generated.c.contains("unsigned long f;"));
+    assert!(generated.cpp.contains("vellum::uint128_t b;"));
+    assert!(
+        generated
+            .c
+            .contains("((vellum_uint128_t)0x1000000000ull << 64 | 0x0ull)")
+    );
+    assert!(
+        generated
+            .cpp
+            .contains("((vellum::uint128_t)0x1000000000ull << 64 | 0x0ull)")
+    );
+    assert!(
+        generated
+            .python
+            .contains("BIG = 1267650600228229401496703205376")
+    );
+    assert!(generated.python.contains("('e', ct.c_long, None)"));
+}
+
+#[test]
+fn platform_constant_out_of_range() {
+    let source = "const A: c_long = 1 << 40;\n";
+    let error = compile("platform_constant_out_of_range", "c", source).unwrap_err();
+    assert!(error.contains("does not fit"), "{}", error);
+}
+
+#[test]
+fn base_structs() {
+    let source = "struct Event { kind: u32 }\n\
+        struct KeyEvent : Event { key: u8 }\n\
+        struct RepeatEvent : KeyEvent { count: u16 }\n";
+    let generated = generate("base_structs", source);
+    assert!(generated.c.contains("KeyEvent base;"));
+    assert!(generated.cpp.contains("struct RepeatEvent : KeyEvent"));
+    assert!(generated.python.contains("class RepeatEvent(KeyEvent):"));
+    assert!(
+        generated
+            .cpp
+            .contains("static_assert(sizeof(RepeatEvent) == sizeof(RepeatEvent::_c_layout));")
+    );
+    assert!(!generated.cpp.contains("tail padding"));
+}
+
+#[test]
+fn derived_struct_layouts() {
+    let source = "struct Header { tag: u8, length: u64 }\n\
+        #[packed]\n\
+        struct PackedHeader { tag: u8, length: u64 }\n\
+        struct Packed : PackedHeader { a: u8, b: u32 }\n\
+        #[align(16)]\n\
+        struct Aligned : Header { #[align(32)] a: u8 }\n\
+        struct Flags : Header { a: u8, b: u32 : 3, c: u32 : 5 }\n\
+        struct Tail : Flags { d: u8 }\n";
+    let generated = generate("derived_struct_layouts", source);
+    assert!(
+        generated
+            .cpp
+            .contains("static_assert(alignof(Aligned) == alignof(Aligned::_c_layout));")
+    );
+}
+
+#[test]
+fn base_field_repeated() {
+    let source = "struct Event { kind: u32 }\nstruct KeyEvent : Event { kind: u32 }\n";
+    let error = compile("base_field_repeated", "c", source).unwrap_err();
+    assert!(error.contains("defined again here"), "{}", error);
+}
+
+#[test]
+fn flag_combinations() {
+    let source = "flags Permissions: u32 { READ = 1, WRITE = 2, READ_WRITE = READ | WRITE, ALL = READ_WRITE | 4 }\n";
+    let generated = generate("flag_combinations", source);
+    assert!(
+        generated
+            .c
+            .contains("#define Permissions_READ_WRITE ((Permissions)3)")
+    );
+    assert!(generated.cpp.contains("enum class Permissions : uint32_t"));
+    assert!(
+        generated
+            .python
+            .contains("class Permissions(enum.IntFlag):")
+    );
+}
+
+#[test]
+fn flags_as_name() {
+    let source = "struct S { flags: u32 }\n";
+    generate("flags_as_name", source);
+}
+
+#[test]
+fn bitfields() {
+    let source = "enum Mode: u8 { Off, On }\n\
+        struct Status {\n\
+            ready: bool : 1,\n\
+            mode: Mode : 3,\n\
+            count: u32 : 20,\n\
+            rest: u32,\n\
+        }\n";
+    let generated = generate("bitfields", source);
+    assert!(generated.c.contains("uint32_t count : 20;"));
+    assert!(generated.cpp.contains("Mode mode : 3;"));
+    assert!(generated.python.contains("('count', ct.c_uint32, 20)"));
+}
+
+#[test]
+fn bitfield_too_wide() {
+    let source = "struct S { a: u8 : 9 }\n";
+    let error = compile("bitfield_too_wide", "c", source).unwrap_err();
+    assert!(error.contains("bitfield width out of range"), "{}", error);
+}
+
+#[test]
+fn optional_values() {
+    let source = "struct Size { width: optional<u32>, height: optional<optional<f64>> }\n\
+        function resize(size: optional<Size>) -> optional<u8>;\n";
+    let generated = generate("optional_values", source);
+    assert!(generated.c.contains("vellum_optional_u32 width;"));
+    assert!(generated.cpp.contains("std::optional<uint8_t> resize("));
+    assert!(generated.python.contains("vellum.Optional(ct.c_uint32)"));
+}
+
+#[test]
+fn optional_abstract_struct() {
+    let source = "struct K;\nfunction f(k: optional<K>) -> void;\n";
+    let error = compile("optional_abstract_struct", "c", source).unwrap_err();
+    assert!(
+        error.contains("optional value must be a sized type"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn optional_payloads_without_default_constructors() {
+    let source = "struct Base { a: u32 }\n\
+        struct S { callback: closure (x: u32) -> void, done: borrowed closure () -> void }\n\
+        function f(x: optional<shared const * Base>, s: optional<S>) -> optional<owned mut * Base>;\n";
+    check_c("optional_payloads_without_default_constructors", source);
+    let cpp = check_cpp("optional_payloads_without_default_constructors", source);
+    assert!(cpp.contains("vellum::optional<S> s"));
+}
+
+#[test]
+fn optional_raii_payloads() {
+    let source = "struct Base { a: u32 }\n\
+        function share(x: optional<shared const * Base>) -> optional<shared const * Base>;\n";
+    let cpp = check_cpp("optional_raii_payloads", source);
+    assert!(cpp.contains("std::optional<vellum::shared<Base const *>> x"));
+
+    // Each reference passed through the ABI is released once
+    let dir = compile("optional_raii_payloads", "cpp", source).unwrap();
+    let main = r#"
+#define VELLUM_STATIC
+#include "optional_raii_payloads.hpp"
+
+static int retains = 0;
+static int releases = 0;
+
+extern "C" vellum::optional<vellum::detail::abi::shared<Base const *>>
+vellum_private_abi::share(vellum::optional<vellum::detail::abi::shared<Base const *>> x) noexcept {
+  return x;
+}
+
+int main() {
+  Base base{1};
+  {
+    std::optional<vellum::shared_ptr<const Base>> x = vellum::shared_ptr<const Base>(
+        &base, [](const Base *) { ++retains; }, [](const Base *) { ++releases; });
+    auto y = share(std::move(x));
+    if (!y || (*y)->a != 1 || share(std::nullopt)) {
+      return 1;
+    }
+  }
+  return retains == 0 && releases == 1 ? 0 : 2;
+}
+"#;
+    fs::write(dir.join("main.cpp"), main).unwrap();
+    run(Command::new("c++")
+        .current_dir(&dir)
+        .args(["-std=c++17", "-Wall", "-Werror", "-o", "main", "-I"])
+        .arg(library().join("c++/include"))
+        .arg("main.cpp"));
+    run(&mut Command::new(dir.join("main")));
+}
+
+#[test]
+fn optional_views() {
+    let source = "struct Base { a: u32 }\n\
+        function take() -> optional<owned mut * Base>;\n";
+    let dir = compile("optional_views", "python", source).unwrap();
+
+    // Getting the value gives a view, which doesn't free the pointer stored in the optional
+    let script = r#"
+import ctypes as ct
+import gc
+import vellum
+from optional_views import Base
+
+deletes = []
+OwnedBase = vellum.Owned(ct.POINTER(Base))
+OptionalBase = vellum.Optional(OwnedBase)
+deleter = OwnedBase.DELETER_TYPE(lambda _: deletes.append(1))
+
+base = Base(1)
+owned = OwnedBase(ct.pointer(base), deleter)
+optional = OptionalBase(True, owned)
+for _ in range(3):
+    assert optional.get().data.contents.a == 1
+gc.collect()
+assert len(deletes) == 0, deletes
+
+del owned
+gc.collect()
+assert len(deletes) == 1, deletes
+"#;
+    run(Command::new("python3")
+        .current_dir(&dir)
+        .env("PYTHONPATH", library().join("python"))
+        .args(["-c", script]));
+}
+
+#[test]
+fn optional_as_name() {
+    let source = "struct S { optional: optional<u32> }\nfunction f(optional: u32) -> void;\n";
+    let generated = generate("optional_as_name", source);
+    assert!(generated.c.contains("vellum_optional_u32 optional;"));
+}
+
+#[test]
+fn interfaces() {
+    let source = "interface Logger {\n\
+            function log(level: u32, msg: const string) -> void;\n\
+            function flush() -> bool;\n\
+        }\n\
+        function set_logger(logger: Logger) -> void;\n";
+    let generated = generate("interfaces", source);
+    assert!(
+        generated
+            .c
+            .contains("void (*log)(void *self, uint32_t level, char const * msg);")
+    );
+    assert!(
+        generated
+            .c
+            .contains("static inline bool Logger_flush(Logger self)")
+    );
+    assert!(
+        generated
+            .cpp
+            .contains("template <typename Object> static Logger from(Object &object)")
+    );
+    assert!(generated.python.contains("class Logger(vellum.Interface):"));
+}
+
+#[test]
+fn interface_method_named_self() {
+    let source = "interface I { function self() -> void; }\n";
+    let error = compile("interface_method_named_self", "c", source).unwrap_err();
+    assert!(
+        error.contains("`self` conflicts with the object implementing the interface"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn interface_as_name() {
+    let source = "struct S { interface: u32 }\nfunction f(interface: S) -> void;\n";
+    let generated = generate("interface_as_name", source);
+    assert!(generated.c.contains("uint32_t interface;"));
+}
+
+#[test]
+fn methods() {
+    let source = "struct KvStore;\n\
+        impl KvStore {\n\
+            function create() -> mut * KvStore;\n\
+            function set(self: mut * KvStore, key: const string, value: const string) -> void;\n\
+            function size(self: const * KvStore) -> usize;\n\
+        }\n";
+    let generated = generate("methods", source);
+    assert!(generated.c.contains("KvStore_set("));
+    assert!(generated.cpp.contains("struct KvStoreHandle {"));
+    assert!(generated.cpp.contains("size_t size("));
+    assert!(generated.python.contains("class KvStoreHandle:"));
+}
+
+#[test]
+fn method_self_not_first() {
+    let source = "struct K;\nimpl K { function f(x: u32, self: mut * K) -> void; }\n";
+    let error = compile("method_self_not_first", "c", source).unwrap_err();
+    assert!(
+        error.contains("`self` must be the first argument"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn impl_as_name() {
+    let source = "struct S { impl: u32 }\nfunction f(impl: S) -> void;\n";
+    let generated = generate("impl_as_name", source);
+    assert!(generated.cpp.contains("uint32_t impl;"));
+}
+
+#[test]
+fn destructors() {
+    let source = "struct KvStore;\n\
+        impl KvStore {\n\
+            function create() -> owned mut * KvStore;\n\
+            function size(self: const * KvStore) -> usize;\n\
+            #[destructor]\n\
+            function destroy(self: mut * KvStore) -> void;\n\
+        }\n";
+    let generated = generate("destructors", source);
+    assert!(generated.cpp.contains("vellum::owned<KvStore *> self;"));
+    assert!(generated.cpp.contains("static KvStoreHandle create("));
+    assert!(generated.python.contains("def close(self):"));
+}
+
+#[test]
+fn destructor_of_struct_with_fields() {
+    let source = "struct K { a: u32 }\n\
+        impl K {\n\
+            #[destructor]\n\
+            function destroy(self: mut * K) -> void;\n\
+        }\n";
+    let error = compile("destructor_of_struct_with_fields", "c", source).unwrap_err();
+    assert!(
+        error.contains("destructors are only for abstract structs"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn shared_pointers() {
+    let source = "struct Cache;\n\
+        function open() -> shared mut * Cache;\n\
+        function share(cache: shared const * Cache) -> void;\n";
+    let generated = generate("shared_pointers", source);
+    assert!(generated.c.contains("vellum_shared_ptr_Cache_ptr open("));
+    assert!(
+        generated
+            .cpp
+            .contains("vellum::shared<Cache const *> cache")
+    );
+    assert!(
+        generated
+            .python
+            .contains("vellum.Shared(ct.POINTER(Cache))")
+    );
+}
+
+#[test]
+fn shared_value() {
+    let source = "struct K { a: shared u32 }\n";
+    let error = compile("shared_value", "c", source).unwrap_err();
+    assert!(
+        error.contains("`shared` must be applied to a pointer"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn shared_views() {
+    let source = "struct Base { a: u32 }\n\
+        struct Holder { base: shared const * Base, callback: closure () -> void }\n";
+    let dir = compile("shared_views", "python", source).unwrap();
+
+    // Reading a field gives a view, which doesn't release the reference held by its container
+    let script = r#"
+import ctypes as ct
+import gc
+import vellum
+from shared_views import Base, Holder
+
+counts = {'retain': 0, 'release': 0}
+SharedBase = dict(Holder._fields_)['base']
+retain = SharedBase.REFCOUNT_TYPE(lambda _: counts.update(retain=counts['retain'] + 1))
+release = SharedBase.REFCOUNT_TYPE(lambda _: counts.update(release=counts['release'] + 1))
+
+base = Base(1)
+shared = SharedBase(ct.pointer(base), retain, release)
+holder = Holder(shared)
+maybe = vellum.Optional(SharedBase)(True, shared)
+for _ in range(3):
+    assert holder.base.data.contents.a == 1
+    assert maybe.get().data.contents.a == 1
+gc.collect()
+assert counts == {'retain': 0, 'release': 0}, counts
+
+del shared
+gc.collect()
+assert counts == {'retain': 0, 'release': 1}, counts
+"#;
+    run(Command::new("python3")
+        .current_dir(&dir)
+        .env("PYTHONPATH", library().join("python"))
+        .args(["-c", script]));
+}
+
+#[test]
+fn shared_as_name() {
+    let source = "struct S { shared: u32 }\nfunction f(shared: S) -> void;\n";
+    let generated = generate("shared_as_name", source);
+    assert!(generated.c.contains("uint32_t shared;"));
+}
+
+#[test]
+fn borrowed_closures() {
+    let source = "function for_each(callback: borrowed closure (x: u32) -> void) -> void;\n\
+        function find(predicate: borrowed closure (x: u32) -> bool) -> u32;\n";
+    let generated = generate("borrowed_closures", source);
+    assert!(
+        generated
+            .c
+            .contains("vellum_borrowed_closure_void_args_u32 callback")
+    );
+    assert!(
+        generated
+            .cpp
+            .contains("vellum::borrowed_closure<bool (uint32_t)> predicate")
+    );
+    assert!(
+        generated
+            .python
+            .contains("vellum.BorrowedClosure(ct.c_bool, ct.c_uint32)")
+    );
+}
+
+#[test]
+fn borrowed_as_name() {
+    let source = "struct S { borrowed: u32 }\nfunction f(borrowed: S, callback: borrowed closure (borrowed: u32) -> void) -> void;\n";
+    let generated = generate("borrowed_as_name", source);
+    assert!(generated.c.contains("uint32_t borrowed;"));
+}
+
+#[test]
+fn constants_in_source_order() {
+    let source = (0..20)
+        .map(|i| format!("const C{}: u32 = {};\n", i, i))
+        .collect::<String>();
+    let header = read(
+        &compile("constants_in_source_order", "c", &source).unwrap(),
+        "constants_in_source_order.h",
+    );
+    let positions = (0..20)
+        .map(|i| header.find(&format!("C{} ", i)).unwrap())
+        .collect::<Vec<_>>();
+    assert!(positions.is_sorted());
+}
+
+#[test]
+fn escaped_keyword_collision() {
+    let source = "struct K { class: u32, class_: u32 }\n";
+    for (language, name) in [("cpp", "C++"), ("python", "Python")] {
+        let error = compile("escaped_keyword_collision", language, source).unwrap_err();
+        let message = format!(
+            "`class` is a keyword in {}, and can't be renamed to `class_`",
+            name
+        );
+        assert!(error.contains(&message), "{}", error);
+    }
+}
+
+#[test]
+fn flag_combination_overlap() {
+    let source = "flags P: u32 { A = 1, B = A | 2, C = 2 }\n";
+    let error = compile("flag_combination_overlap", "c", source).unwrap_err();
+    assert!(
+        error.contains("flag `C` overlaps another flag"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn handle_method_named_close() {
+    let source = "struct K;\n\
+        impl K {\n\
+            function close(self: mut * K) -> void;\n\
+            #[destructor]\n\
+            function destroy(self: mut * K) -> void;\n\
+        }\n";
+    compile("handle_method_named_close", "cpp", source).unwrap();
+    let error = compile("handle_method_named_close", "python", source).unwrap_err();
+    assert!(
+        error.contains("handle `KHandle` can't have a method named `close` in Python"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn example() {
+    let abi =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples/multi-language/mylibrary.abi");
+    for (language, file) in [
+        ("c", "mylibrary.h"),
+        ("cpp", "mylibrary.hpp"),
+        ("python", "mylibrary.py"),
+    ] {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+            .join("compile")
+            .join(format!("example_{}", language));
+        fs::create_dir_all(&dir).unwrap();
+        let dir = compile_file(language, &abi, &dir).unwrap();
+        let output = read(&dir, file);
+        assert!(output.contains("KvStore_destroy"));
+        if language != "c" {
+            assert!(output.contains("KvStoreHandle"));
+        }
+    }
+}
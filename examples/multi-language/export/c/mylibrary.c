@@ -8,31 +8,26 @@ struct KvStore {
     size_t cap;
 };
 
-static void kvstore_free(struct KvStore *s) {
-    if (!s) return;
-    for (size_t i = 0; i < s->len; ++i) {
-        // keys/values are strdup'd
-        free((void*)s->entries[i].key);
-        free((void*)s->entries[i].value);
-    }
-    free(s->entries);
-    free(s);
-}
+// Stats are handed out as shared pointers, so they carry a reference count
+struct SharedKvStats {
+    KvStats stats;
+    size_t refs;
+};
 
 static void free_kventry_slice(vellum_slice_mut_KvEntry slice) {
-    // Only free the backing array allocated in kv_entries
+    // Only free the backing array allocated in KvStore_entries
     free(slice.data);
 }
 
-vellum_owned_ptr_KvStore_ptr vellum_export_kv_create(void) {
-    struct KvStore *s = (struct KvStore*)malloc(sizeof(struct KvStore));
-    s->entries = NULL;
-    s->len = 0;
-    s->cap = 0;
-    vellum_owned_ptr_KvStore_ptr out;
-    out.data = s;
-    out.deleter = (void(*)(struct KvStore*))kvstore_free;
-    return out;
+static void retain_stats(KvStats const *stats) {
+    ((struct SharedKvStats*)(void*)stats)->refs += 1;
+}
+
+static void release_stats(KvStats const *stats) {
+    struct SharedKvStats *shared = (struct SharedKvStats*)(void*)stats;
+    if (--shared->refs == 0) {
+        free(shared);
+    }
 }
 
 static char* dup_cstr(const char* s) {
@@ -44,60 +39,110 @@ static char* dup_cstr(const char* s) {
     return p;
 }
 
-void vellum_export_kv_set(struct KvStore *store, const char *key, const char *value) {
-    if (!store || !key) return;
+static struct KvEntry *find_entry(const struct KvStore *store, const char *key) {
+    if (!store || !key) return NULL;
     // linear search
     for (size_t i = 0; i < store->len; ++i) {
         if (strcmp(store->entries[i].key, key) == 0) {
-            // replace value
-            free((void*)store->entries[i].value);
-            store->entries[i].value = dup_cstr(value);
-            return;
+            return &store->entries[i];
         }
     }
+    return NULL;
+}
+
+KvStore *vellum_export_KvStore_create(void) {
+    struct KvStore *s = (struct KvStore*)malloc(sizeof(struct KvStore));
+    s->entries = NULL;
+    s->len = 0;
+    s->cap = 0;
+    return s;
+}
+
+void vellum_export_KvStore_destroy(struct KvStore *s) {
+    if (!s) return;
+    for (size_t i = 0; i < s->len; ++i) {
+        // keys/values are strdup'd
+        free((void*)s->entries[i].key);
+        free((void*)s->entries[i].value);
+    }
+    free(s->entries);
+    free(s);
+}
+
+SetResult vellum_export_KvStore_set(struct KvStore *store, const char *key, const char *value) {
+    struct KvEntry *entry = find_entry(store, key);
+    if (entry) {
+        // replace value
+        free((void*)entry->value);
+        entry->value = dup_cstr(value);
+        return SetResult_Replaced;
+    }
     if (store->len == store->cap) {
         size_t new_cap = store->cap ? store->cap * 2 : 8;
         void* new_mem = realloc(store->entries, new_cap * sizeof(struct KvEntry));
-        if (!new_mem) return;
+        if (!new_mem) return SetResult_Inserted;
         store->entries = (struct KvEntry*)new_mem;
         store->cap = new_cap;
     }
     store->entries[store->len].key = dup_cstr(key);
     store->entries[store->len].value = dup_cstr(value);
     store->len += 1;
+    return SetResult_Inserted;
 }
 
-const char *vellum_export_kv_get(const struct KvStore *store, const char *key) {
-    if (!store || !key) return NULL;
+vellum_owned_ptr_KvStore_ptr vellum_export_KvStore_copy(const struct KvStore *store) {
+    struct KvStore *s = vellum_export_KvStore_create();
     for (size_t i = 0; i < store->len; ++i) {
-        if (strcmp(store->entries[i].key, key) == 0) {
-            return store->entries[i].value;
-        }
+        vellum_export_KvStore_set(s, store->entries[i].key, store->entries[i].value);
     }
-    return NULL;
+    vellum_owned_ptr_KvStore_ptr out;
+    out.data = s;
+    out.deleter = vellum_export_KvStore_destroy;
+    return out;
 }
 
-void vellum_export_kv_delete(struct KvStore *store, const char *key) {
-    if (!store || !key) return;
-    for (size_t i = 0; i < store->len; ++i) {
-        if (strcmp(store->entries[i].key, key) == 0) {
-            free((void*)store->entries[i].key);
-            free((void*)store->entries[i].value);
-            // move last into this slot
-            if (i + 1 < store->len) {
-                store->entries[i] = store->entries[store->len - 1];
-            }
-            store->len -= 1;
-            return;
-        }
+const char *vellum_export_KvStore_get(const struct KvStore *store, const char *key) {
+    struct KvEntry *entry = find_entry(store, key);
+    return entry ? entry->value : NULL;
+}
+
+Lookup vellum_export_KvStore_find(const struct KvStore *store, const char *key) {
+    Lookup out;
+    struct KvEntry *entry = find_entry(store, key);
+    if (entry) {
+        out.tag = Lookup_tag_found;
+        out.value.found = *entry;
+    } else {
+        out.tag = Lookup_tag_missing;
     }
+    return out;
 }
 
-size_t vellum_export_kv_size(const struct KvStore *store) {
+vellum_optional_usize vellum_export_KvStore_length(const struct KvStore *store, const char *key) {
+    vellum_optional_usize out = {false, 0};
+    struct KvEntry *entry = find_entry(store, key);
+    if (entry) {
+        out.present = true;
+        out.value = strlen(entry->value);
+    }
+    return out;
+}
+
+void vellum_export_KvStore_delete(struct KvStore *store, const char *key) {
+    struct KvEntry *entry = find_entry(store, key);
+    if (!entry) return;
+    free((void*)entry->key);
+    free((void*)entry->value);
+    // move last into this slot
+    *entry = store->entries[store->len - 1];
+    store->len -= 1;
+}
+
+size_t vellum_export_KvStore_size(const struct KvStore *store) {
     return store ? store->len : 0;
 }
 
-vellum_owned_slice_mut_KvEntry vellum_export_kv_entries(const struct KvStore *store) {
+vellum_owned_slice_mut_KvEntry vellum_export_KvStore_entries(const struct KvStore *store) {
     vellum_owned_slice_mut_KvEntry out;
     if (!store || store->len == 0) {
         out.slice_data.data = NULL;
@@ -121,7 +166,40 @@ vellum_owned_slice_mut_KvEntry vellum_export_kv_entries(const struct KvStore *st
     return out;
 }
 
-void vellum_export_kv_clear(struct KvStore *store) {
+void vellum_export_KvStore_for_each(
+    const struct KvStore *store,
+    vellum_borrowed_closure_void_args_const_char_ptr_const_char_ptr callback) {
+    for (size_t i = 0; i < store->len; ++i) {
+        callback.call(callback.state, store->entries[i].key, store->entries[i].value);
+    }
+}
+
+size_t vellum_export_KvStore_count(const struct KvStore *store, KvFilter filter) {
+    size_t count = 0;
+    for (size_t i = 0; i < store->len; ++i) {
+        if (KvFilter_keep(filter, store->entries[i].key, store->entries[i].value)) {
+            count += 1;
+        }
+    }
+    return count;
+}
+
+vellum_shared_ptr_const_KvStats_ptr vellum_export_KvStore_stats(const struct KvStore *store) {
+    struct SharedKvStats *shared = (struct SharedKvStats*)malloc(sizeof(struct SharedKvStats));
+    shared->stats.entries = store->len;
+    shared->stats.letters = 0;
+    for (size_t i = 0; i < store->len; ++i) {
+        shared->stats.letters += strlen(store->entries[i].value);
+    }
+    shared->refs = 1;
+    vellum_shared_ptr_const_KvStats_ptr out;
+    out.data = &shared->stats;
+    out.retain = retain_stats;
+    out.release = release_stats;
+    return out;
+}
+
+void vellum_export_KvStore_clear(struct KvStore *store) {
     if (!store) return;
     for (size_t i = 0; i < store->len; ++i) {
         free((void*)store->entries[i].key);
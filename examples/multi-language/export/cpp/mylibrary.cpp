@@ -9,32 +9,80 @@ struct KvStore {
     std::map<std::string, std::string> map;
 };
 
+namespace {
+
+// Stats are handed out as shared pointers, so they carry a reference count
+struct SharedKvStats {
+    KvStats stats;
+    size_t refs;
+};
+
+void retain_stats(const KvStats *stats) {
+    reinterpret_cast<SharedKvStats *>(const_cast<KvStats *>(stats))->refs += 1;
+}
+
+void release_stats(const KvStats *stats) {
+    auto shared = reinterpret_cast<SharedKvStats *>(const_cast<KvStats *>(stats));
+    if (--shared->refs == 0) {
+        delete shared;
+    }
+}
+
+} // namespace
+
 namespace vellum_export {
 
-vellum::owned<KvStore *> kv_create() noexcept {
-    return std::make_unique<KvStore>();
+KvStore *KvStore_create() noexcept {
+    return new KvStore();
+}
+
+vellum::owned<KvStore *> KvStore_copy(const KvStore *store) noexcept {
+    return std::make_unique<KvStore>(*store);
+}
+
+void KvStore_destroy(KvStore *store) noexcept {
+    delete store;
 }
 
-void kv_set(KvStore *store, const char *key, const char *value) noexcept {
-    store->map.emplace(std::make_pair(key, value));
+SetResult KvStore_set(KvStore *store, const char *key, const char *value) noexcept {
+    auto inserted = store->map.insert_or_assign(key, value).second;
+    return inserted ? SetResult::Inserted : SetResult::Replaced;
 }
 
-const char *kv_get(const KvStore *store, const char *key) noexcept {
+const char *KvStore_get(const KvStore *store, const char *key) noexcept {
     if (store->map.count(key)) {
         return store->map.at(key).c_str();
     }
     return nullptr;
 }
 
-void kv_delete(KvStore *store, const char *key) noexcept {
+Lookup KvStore_find(const KvStore *store, const char *key) noexcept {
+    auto it = store->map.find(key);
+    if (it == store->map.end()) {
+        return Lookup::make_missing();
+    }
+    KvEntry entry;
+    entry.key = it->first.c_str();
+    entry.value = it->second.c_str();
+    return Lookup::make_found(entry);
+}
+
+vellum::optional<size_t> KvStore_length(const KvStore *store, const char *key) noexcept {
+    if (store->map.count(key)) {
+        return store->map.at(key).size();
+    }
+    return std::nullopt;
+}
+
+void KvStore_delete(KvStore *store, const char *key) noexcept {
     store->map.erase(key);
 }
 
-size_t kv_size(const KvStore *store) noexcept {
+size_t KvStore_size(const KvStore *store) noexcept {
     return store->map.size();
 }
 
-vellum::owned_slice<KvEntry> kv_entries(const KvStore *store) noexcept {
+vellum::owned_slice<KvEntry> KvStore_entries(const KvStore *store) noexcept {
     std::vector<KvEntry> entries;
     entries.reserve(store->map.size());
     for (const auto &kv : store->map) {
@@ -45,7 +93,34 @@ vellum::owned_slice<KvEntry> kv_entries(const KvStore *store) noexcept {
     return entries;
 }
 
-void kv_clear(KvStore *store) noexcept {
+void KvStore_for_each(const KvStore *store,
+                      vellum::borrowed_closure<void(const char *, const char *)> callback) noexcept {
+    for (const auto &kv : store->map) {
+        callback(kv.first.c_str(), kv.second.c_str());
+    }
+}
+
+size_t KvStore_count(const KvStore *store, KvFilter filter) noexcept {
+    size_t count = 0;
+    for (const auto &kv : store->map) {
+        if (filter.keep(filter.self, kv.first.c_str(), kv.second.c_str())) {
+            count += 1;
+        }
+    }
+    return count;
+}
+
+vellum::shared<const KvStats *> KvStore_stats(const KvStore *store) noexcept {
+    auto shared = new SharedKvStats{};
+    shared->stats.entries = store->map.size();
+    for (const auto &kv : store->map) {
+        shared->stats.letters += kv.second.size();
+    }
+    shared->refs = 1;
+    return vellum::shared<const KvStats *>(&shared->stats, retain_stats, release_stats);
+}
+
+void KvStore_clear(KvStore *store) noexcept {
     store->map.clear();
 }
 
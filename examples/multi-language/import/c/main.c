@@ -2,27 +2,83 @@
 #include <string.h>
 #include "mylibrary.h"
 
+static void visit(void *state, const char *key, const char *value) {
+    (void)state;
+    (void)value;
+    printf("Visited %s\n", key);
+}
+
+static bool longer_than(void *self, const char *key, const char *value) {
+    (void)key;
+    return strlen(value) > *(size_t*)self;
+}
+
 int main(void) {
     // Create store via C ABI
-    vellum_owned_ptr_KvStore_ptr store = kv_create();
-    kv_set(store.data, "Alice", "teacher");
-    kv_set(store.data, "Bob", "musician");
-    kv_set(store.data, "Charlie", "chef");
-    kv_set(store.data, "Dan", "astronaut");
-    kv_delete(store.data, "Dan");
-
-    size_t n = kv_size(store.data);
+    KvStore *store = KvStore_create();
+    KvStore_set(store, "Alice", "teacher");
+    KvStore_set(store, "Bob", "musician");
+    KvStore_set(store, "Charlie", "chef");
+    KvStore_set(store, "Dan", "astronaut");
+    KvStore_delete(store, "Dan");
+
+    size_t n = KvStore_size(store);
     printf("%zu entries\n", n);
-    const char* alice = kv_get(store.data, "Alice");
+    const char* alice = KvStore_get(store, "Alice");
     printf("Alice is a %s\n", alice ? alice : "(null)");
 
-    vellum_owned_slice_mut_KvEntry entries = kv_entries(store.data);
+    vellum_owned_slice_mut_KvEntry entries = KvStore_entries(store);
     for (size_t i = 0; i < entries.slice_data.len; ++i) {
         struct KvEntry e = entries.slice_data.data[i];
         printf("%s is a %s\n", e.key, e.value);
     }
-    // free owned slice and store via deleters
+    // free owned slice via its deleter
     if (entries.deleter) entries.deleter(entries.slice_data);
-    if (store.deleter) store.deleter(store.data);
+
+    if (KvStore_set(store, "Alice", "teacher") == SetResult_Replaced) {
+        printf("Alice was replaced\n");
+    }
+
+    const char *names[] = {"Bob", "Dan"};
+    for (size_t i = 0; i < 2; ++i) {
+        Lookup lookup = KvStore_find(store, names[i]);
+        if (lookup.tag == Lookup_tag_found) {
+            printf("Found %s, a %s\n", lookup.value.found.key, lookup.value.found.value);
+        } else {
+            printf("%s is missing\n", names[i]);
+        }
+    }
+
+    const char *lengths[] = {"Charlie", "Dan"};
+    for (size_t i = 0; i < 2; ++i) {
+        vellum_optional_usize length = KvStore_length(store, lengths[i]);
+        if (length.present) {
+            printf("%s's value has %zu letters\n", lengths[i], length.value);
+        } else {
+            printf("%s has no value\n", lengths[i]);
+        }
+    }
+
+    vellum_borrowed_closure_void_args_const_char_ptr_const_char_ptr callback = {visit, NULL};
+    KvStore_for_each(store, callback);
+
+    size_t min_length = 5;
+    KvFilter filter = {longer_than, &min_length};
+    printf("%zu values are longer than %zu letters\n", KvStore_count(store, filter), min_length);
+
+    // hold a second reference to the stats, and release both
+    vellum_shared_ptr_const_KvStats_ptr stats = KvStore_stats(store);
+    if (stats.retain) stats.retain(stats.data);
+    printf("%zu entries with %zu letters\n", stats.data->entries, stats.data->letters);
+    if (stats.release) stats.release(stats.data);
+    if (stats.release) stats.release(stats.data);
+
+    vellum_owned_ptr_KvStore_ptr copy = KvStore_copy(store);
+    KvStore_clear(store);
+    printf("The copy has %zu entries after clearing the store\n", KvStore_size(copy.data));
+
+    // free the copy via its deleter, and the store via its destructor
+    if (copy.deleter) copy.deleter(copy.data);
+    KvStore_destroy(store);
     return 0;
 }
@@ -1,21 +1,71 @@
 #define VELLUM_DYNAMIC
 
 #include "mylibrary.hpp"
+#include <cstring>
 #include <iostream>
+#include <string>
+
+struct LongerThan {
+    size_t min_length;
+
+    bool keep(const char *, const char *value) {
+        return std::strlen(value) > min_length;
+    }
+};
 
 int main() {
-    auto store = kv_create().to_unique_ptr();
-    kv_set(store.get(), "Alice", "teacher");
-    kv_set(store.get(), "Bob", "musician");
-    kv_set(store.get(), "Charlie", "chef");
-    kv_set(store.get(), "Dan", "astronaut");
+    KvStoreHandle store(KvStoreHandle::create());
+    store.set("Alice", "teacher");
+    store.set("Bob", "musician");
+    store.set("Charlie", "chef");
+    store.set("Dan", "astronaut");
 
-    kv_delete(store.get(), "Dan");
+    store.delete_("Dan");
 
-    std::cout << kv_size(store.get()) << " entries" << std::endl;
-    std::cout << "Alice is a " << kv_get(store.get(), "Alice") << std::endl;
+    std::cout << store.size() << " entries" << std::endl;
+    std::cout << "Alice is a " << store.get("Alice") << std::endl;
 
-    for (const auto &entry : kv_entries(store.get())) {
+    for (const auto &entry : store.entries()) {
         std::cout << entry.key << " is a " << entry.value << std::endl;
     }
+
+    if (store.set("Alice", "teacher") == SetResult::Replaced) {
+        std::cout << "Alice was replaced" << std::endl;
+    }
+
+    for (const char *name : {"Bob", "Dan"}) {
+        auto lookup = store.find(name);
+        if (lookup.is_found()) {
+            const auto &entry = lookup.get_found();
+            std::cout << "Found " << entry.key << ", a " << entry.value << std::endl;
+        } else {
+            std::cout << name << " is missing" << std::endl;
+        }
+    }
+
+    for (const char *name : {"Charlie", "Dan"}) {
+        if (auto length = store.length(name)) {
+            std::cout << name << "'s value has " << *length << " letters" << std::endl;
+        } else {
+            std::cout << name << " has no value" << std::endl;
+        }
+    }
+
+    store.for_each([](const char *key, const char *) {
+        std::cout << "Visited " << key << std::endl;
+    });
+
+    LongerThan filter{5};
+    std::cout << store.count(KvFilter::from(filter)) << " values are longer than "
+              << filter.min_length << " letters" << std::endl;
+
+    auto stats = store.stats();
+    auto stats_copy = stats;
+    std::cout << stats_copy->entries << " entries with " << stats_copy->letters << " letters"
+              << std::endl;
+
+    auto copy = store.copy();
+    store.clear();
+    std::cout << "The copy has " << copy.size() << " entries after clearing the store"
+              << std::endl;
 }
@@ -22,26 +22,71 @@ EXPORT_CHOICES = tuple(
 DEFAULT_EXPORT = "c"
 
 
-def run_with(lib) -> None:
-    """Exercise the key-value store with the provided library handle."""
+class LongerThan:
+    """A filter keeping values longer than ``min_length``."""
 
-    store = lib.kv_create()
-    lib.kv_set(store.data, b"Alice", b"teacher")
-    lib.kv_set(store.data, b"Bob", b"musician")
-    lib.kv_set(store.data, b"Charlie", b"chef")
-    lib.kv_set(store.data, b"Dan", b"astronaut")
+    def __init__(self, min_length: int) -> None:
+        self.min_length = min_length
 
-    lib.kv_delete(store.data, b"Dan")
+    def keep(self, key: bytes, value: bytes) -> bool:
+        return len(value) > self.min_length
 
-    print(f"{lib.kv_size(store.data)} entries")
 
-    alice = lib.kv_get(store.data, b"Alice").decode("utf-8")
-    print(f"Alice is a {alice}")
+def run_with(lib) -> None:
+    """Exercise the key-value store with the provided library handle."""
 
-    for entry in lib.kv_entries(store.data):
-        key = entry.key.decode("utf-8")
-        value = entry.value.decode("utf-8")
-        print(f"{key} is a {value}")
+    with mylibrary.KvStoreHandle(lib, mylibrary.KvStoreHandle.create(lib)) as store:
+        store.set(b"Alice", b"teacher")
+        store.set(b"Bob", b"musician")
+        store.set(b"Charlie", b"chef")
+        store.set(b"Dan", b"astronaut")
+
+        store.delete(b"Dan")
+
+        print(f"{store.size()} entries")
+
+        alice = store.get(b"Alice").decode("utf-8")
+        print(f"Alice is a {alice}")
+
+        for entry in store.entries():
+            key = entry.key.decode("utf-8")
+            value = entry.value.decode("utf-8")
+            print(f"{key} is a {value}")
+
+        if store.set(b"Alice", b"teacher") == mylibrary.SetResult.Replaced:
+            print("Alice was replaced")
+
+        for name in ("Bob", "Dan"):
+            lookup = store.find(name.encode("utf-8"))
+            if lookup.tag == mylibrary.Lookup.Tag.found:
+                key = lookup.value.key.decode("utf-8")
+                value = lookup.value.value.decode("utf-8")
+                print(f"Found {key}, a {value}")
+            else:
+                print(f"{name} is missing")
+
+        for name in ("Charlie", "Dan"):
+            length = store.length(name.encode("utf-8")).get()
+            if length is not None:
+                print(f"{name}'s value has {length} letters")
+            else:
+                print(f"{name} has no value")
+
+        store.for_each(lambda key, value: print(f"Visited {key.decode('utf-8')}"))
+
+        longer_than = LongerThan(5)
+        count = store.count(mylibrary.KvFilter.implement(longer_than))
+        print(f"{count} values are longer than {longer_than.min_length} letters")
+
+        stats = store.stats()
+        stats_copy = stats.copy()
+        stats.free()
+        print(f"{stats_copy.data.contents.entries} entries with {stats_copy.data.contents.letters} letters")
+        stats_copy.free()
+
+        with store.copy() as copy:
+            store.clear()
+            print(f"The copy has {copy.size()} entries after clearing the store")
 
 
 def resolve_library_path(export: str) -> Path:
@@ -1,4 +1,6 @@
 #pragma once
 #include "vellum/abi.hpp"
 #include "vellum/functions.hpp"
+#include "vellum/integers.hpp"
+#include "vellum/optional.hpp"
 #include "vellum/pointers.hpp"
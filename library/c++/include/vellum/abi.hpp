@@ -10,7 +10,9 @@ namespace vellum {
 // Forward declarations for RAII wrappers used in conversions
 template <typename> struct owned_ptr;
 template <typename> struct owned_slice;
+template <typename> struct shared_ptr;
 template <typename> struct closure;
+template <typename> struct borrowed_closure;
 
 namespace detail {
 namespace abi {
@@ -77,6 +79,22 @@ template <typename T> struct [[nodiscard]] owned_slice {
   operator ::vellum::owned_slice<T>() && noexcept;
 };
 
+// Shared pointer POD: pointer + retain(data) + release(data)
+template <typename T> struct [[nodiscard]] shared_ptr {
+  using element_type = T;
+  using pointer = T *;
+  using retain_type = void (*)(T *);
+  using release_type = void (*)(T *);
+
+  pointer data;
+  retain_type retain;
+  release_type release;
+
+  // Conversions to/from RAII wrappers (definitions in pointers.hpp)
+  shared_ptr(::vellum::shared_ptr<T> &&other) noexcept;
+  operator ::vellum::shared_ptr<T>() && noexcept;
+};
+
 // Closure POD
 template <typename> struct [[nodiscard]] closure;
 
@@ -96,6 +114,24 @@ struct [[nodiscard]] closure<R(Args...)> {
   operator ::vellum::closure<R(Args...)>() && noexcept;
 };
 
+// Borrowed closure POD: a closure without a deleter
+template <typename> struct borrowed_closure;
+
+template <typename R, typename... Args>
+struct borrowed_closure<R(Args...)> {
+  using result_type = R;
+  using function_type = R(Args...);
+  using caller_type = R (*)(void *, Args...);
+
+  caller_type caller;
+  void *state;
+
+  // Conversions to/from RAII borrowed closure (definitions in functions.hpp)
+  borrowed_closure(
+      const ::vellum::borrowed_closure<R(Args...)> &other) noexcept;
+  operator ::vellum::borrowed_closure<R(Args...)>() const noexcept;
+};
+
 // owned<T> selector similar to RAII, but resolves to POD owned_* types
 template <typename T> struct is_slice : std::false_type {};
 template <typename T> struct is_slice<slice<T>> : std::true_type {};
@@ -115,13 +151,19 @@ using owned_selector =
 
 template <typename T> using owned = owned_selector<T>;
 
+template <typename T> using shared = shared_ptr<std::remove_pointer_t<T>>;
+
 // ABI invariants
 static_assert(std::is_standard_layout_v<owned_ptr<int>>);
 static_assert(std::is_trivially_copyable_v<owned_ptr<int>>);
 static_assert(std::is_standard_layout_v<owned_slice<int>>);
 static_assert(std::is_trivially_copyable_v<owned_slice<int>>);
+static_assert(std::is_standard_layout_v<shared_ptr<int>>);
+static_assert(std::is_trivially_copyable_v<shared_ptr<int>>);
 static_assert(std::is_standard_layout_v<closure<int(int)>>);
 static_assert(std::is_trivially_copyable_v<closure<int(int)>>);
+static_assert(std::is_standard_layout_v<borrowed_closure<int(int)>>);
+static_assert(std::is_trivially_copyable_v<borrowed_closure<int(int)>>);
 
 } // namespace abi
 } // namespace detail
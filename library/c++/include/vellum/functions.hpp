@@ -1,5 +1,6 @@
 #include <cstdlib>
 #include <functional>
+#include <memory>
 #include <stdexcept>
 #include <type_traits>
 #include <utility>
@@ -9,6 +10,7 @@
 namespace vellum {
 
 template <typename> struct closure;
+template <typename> struct borrowed_closure;
 
 namespace detail {
 
@@ -56,6 +58,18 @@ R call_fn_pointer(void *state, Args... args) noexcept try {
 } catch (...) {
   std::abort();
 }
+
+template <typename F, typename R, typename... Args>
+R call_borrowed(void *state, Args... args) noexcept try {
+  auto &f = *static_cast<F *>(state);
+  if constexpr (std::is_void_v<R>) {
+    std::invoke(f, std::forward<Args>(args)...);
+  } else {
+    return std::invoke(f, std::forward<Args>(args)...);
+  }
+} catch (...) {
+  std::abort();
+}
 } // namespace detail
 
 template <typename T> using function = typename detail::function_ptr<T>::type;
@@ -191,6 +205,57 @@ static_assert(std::is_nothrow_move_constructible_v<closure<int(int)>>);
 static_assert(std::is_nothrow_move_assignable_v<closure<int(int)>>);
 static_assert(std::is_nothrow_swappable_v<closure<int(int)>>);
 
+// A closure that refers to a callable without owning it, so the callable must
+// outlive it.  Constructing one never allocates.
+template <typename R, typename... Args> struct borrowed_closure<R(Args...)> {
+  using result_type = R;
+  using function_type = R(Args...);
+  using caller_type = R (*)(void *, Args...);
+
+  caller_type caller;
+  void *state;
+
+  template <typename F,
+            typename = std::enable_if_t<
+                !std::is_same_v<std::decay_t<F>, borrowed_closure> &&
+                std::is_invocable_r_v<R, F &, Args...>>>
+  borrowed_closure(F &&f) noexcept {
+    using callable = std::remove_reference_t<F>;
+    if constexpr (std::is_function_v<callable>) {
+      caller = &detail::call_fn_pointer<R, Args...>;
+      state = reinterpret_cast<void *>(&f);
+    } else if constexpr (std::is_pointer_v<callable> &&
+                         std::is_function_v<std::remove_pointer_t<callable>>) {
+      caller = &detail::call_fn_pointer<R, Args...>;
+      state = reinterpret_cast<void *>(f);
+    } else {
+      caller = &detail::call_borrowed<callable, R, Args...>;
+      state =
+          const_cast<void *>(static_cast<const void *>(std::addressof(f)));
+    }
+  }
+
+  borrowed_closure(caller_type caller, void *state) noexcept
+      : caller(caller), state(state) {}
+
+  R operator()(Args... args) const noexcept {
+    if (!caller) {
+      std::abort();
+    }
+
+    if constexpr (std::is_void_v<R>) {
+      caller(state, std::forward<Args>(args)...);
+    } else {
+      return caller(state, std::forward<Args>(args)...);
+    }
+  }
+
+  explicit operator bool() const noexcept { return caller != nullptr; }
+};
+
+static_assert(std::is_standard_layout_v<borrowed_closure<int(int)>>);
+static_assert(std::is_trivially_copyable_v<borrowed_closure<int(int)>>);
+
 // ABI invariants for POD closure
 static_assert(std::is_standard_layout_v<detail::abi::closure<int(int)>>);
 static_assert(std::is_trivially_copyable_v<detail::abi::closure<int(int)>>);
@@ -215,4 +280,16 @@ inline detail::abi::closure<R(Args...)>::operator ::vellum::closure<
   return out;
 }
 
+// detail::abi::borrowed_closure <-> RAII borrowed closure conversions
+template <typename R, typename... Args>
+inline detail::abi::borrowed_closure<R(Args...)>::borrowed_closure(
+    const ::vellum::borrowed_closure<R(Args...)> &other) noexcept
+    : caller(other.caller), state(other.state) {}
+
+template <typename R, typename... Args>
+inline detail::abi::borrowed_closure<R(Args...)>::operator ::vellum::
+    borrowed_closure<R(Args...)>() const noexcept {
+  return ::vellum::borrowed_closure<R(Args...)>(caller, state);
+}
+
 } // namespace vellum
@@ -0,0 +1,11 @@
+#pragma once
+
+namespace vellum {
+
+// 128-bit integers are a compiler extension, and are only declared where supported.
+#if defined(__SIZEOF_INT128__)
+__extension__ typedef __int128 int128_t;
+__extension__ typedef unsigned __int128 uint128_t;
+#endif
+
+} // namespace vellum
@@ -0,0 +1,68 @@
+#pragma once
+
+#include <new>
+#include <optional>
+#include <type_traits>
+#include <utility>
+
+namespace vellum {
+
+// Optional value POD: presence flag + value
+//
+// The value is only constructed when present, so `T` needn't be default constructible.
+template <typename T> struct optional {
+  using value_type = T;
+
+  bool present;
+  union {
+    T value;
+  };
+
+  optional() noexcept : present(false) {}
+  optional(std::nullopt_t) noexcept : optional() {}
+  optional(const T &v) noexcept(std::is_nothrow_copy_constructible_v<T>)
+      : present(true), value(v) {}
+  optional(const std::optional<T> &other) : present(other.has_value()) {
+    if (present) {
+      new (&value) T(*other);
+    }
+  }
+
+  // Conversions from optional RAII wrappers, such as `std::optional<vellum::owned_ptr<U>>`
+  template <typename U,
+            typename = std::enable_if_t<!std::is_same_v<U, T> &&
+                                        std::is_constructible_v<T, U &&>>>
+  optional(std::optional<U> &&other) : present(other.has_value()) {
+    if (present) {
+      new (&value) T(std::move(*other));
+    }
+  }
+
+  operator std::optional<T>() const {
+    if (present) {
+      return value;
+    }
+    return std::nullopt;
+  }
+
+  // Conversions to optional RAII wrappers, which take ownership of the value
+  template <typename U,
+            typename = std::enable_if_t<!std::is_same_v<U, T> &&
+                                        std::is_constructible_v<U, T &&>>>
+  operator std::optional<U>() && {
+    if (present) {
+      return U(std::move(value));
+    }
+    return std::nullopt;
+  }
+
+  bool has_value() const noexcept { return present; }
+  explicit operator bool() const noexcept { return present; }
+  T value_or(T other) const { return present ? value : std::move(other); }
+};
+
+// ABI invariants
+static_assert(std::is_standard_layout_v<optional<int>>);
+static_assert(std::is_trivially_copyable_v<optional<int>>);
+
+} // namespace vellum
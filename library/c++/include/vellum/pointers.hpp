@@ -1,4 +1,5 @@
 #include <cstddef>
+#include <cstdlib>
 #include <iterator>
 #include <memory>
 #include <type_traits>
@@ -12,6 +13,26 @@ namespace vellum {
 // Bring slice into this namespace for ergonomic use
 using detail::abi::slice;
 
+// A pointer that is checked to be non-null when constructed.
+template <typename T> struct not_null {
+  static_assert(std::is_pointer_v<T>, "not_null requires a pointer type");
+
+  not_null(T ptr) noexcept : data(ptr) {
+    if (!data) {
+      std::abort();
+    }
+  }
+  not_null(std::nullptr_t) = delete;
+
+  T get() const noexcept { return data; }
+  operator T() const noexcept { return data; }
+  T operator->() const noexcept { return data; }
+  decltype(auto) operator*() const noexcept { return *data; }
+
+private:
+  T data;
+};
+
 template <typename T> struct owned_ptr {
   using element_type = T;
   using pointer = T *;
@@ -96,6 +117,78 @@ template <typename T> struct owned_ptr {
   }
 };
 
+// A reference-counted pointer. Copies retain the data, and each copy releases
+// it when destroyed.
+template <typename T> struct shared_ptr {
+  using element_type = T;
+  using pointer = T *;
+  using retain_type = void (*)(T *);
+  using release_type = void (*)(T *);
+
+  pointer data;
+  retain_type retain;
+  release_type release;
+
+  shared_ptr() noexcept : data(nullptr), retain(nullptr), release(nullptr) {}
+  // Adopts a reference, without retaining it.
+  shared_ptr(pointer ptr, retain_type retain_fn,
+             release_type release_fn) noexcept
+      : data(ptr), retain(retain_fn), release(release_fn) {}
+
+  ~shared_ptr() { reset(); }
+
+  shared_ptr(const shared_ptr &other) noexcept
+      : data(other.data), retain(other.retain), release(other.release) {
+    if (data && retain) {
+      retain(data);
+    }
+  }
+
+  shared_ptr &operator=(const shared_ptr &other) noexcept {
+    if (this != &other) {
+      shared_ptr(other).swap(*this);
+    }
+    return *this;
+  }
+
+  shared_ptr(shared_ptr &&other) noexcept
+      : data(other.data), retain(other.retain), release(other.release) {
+    other.data = nullptr;
+    other.retain = nullptr;
+    other.release = nullptr;
+  }
+
+  shared_ptr &operator=(shared_ptr &&other) noexcept {
+    if (this != &other) {
+      reset();
+      swap(other);
+    }
+    return *this;
+  }
+
+  void reset() noexcept {
+    if (data && release) {
+      release(data);
+    }
+    data = nullptr;
+    retain = nullptr;
+    release = nullptr;
+  }
+
+  void swap(shared_ptr &other) noexcept {
+    std::swap(data, other.data);
+    std::swap(retain, other.retain);
+    std::swap(release, other.release);
+  }
+
+  pointer get() const noexcept { return data; }
+  retain_type get_retain() const noexcept { return retain; }
+  release_type get_release() const noexcept { return release; }
+  explicit operator bool() const noexcept { return data != nullptr; }
+  T &operator*() const noexcept { return *data; }
+  pointer operator->() const noexcept { return data; }
+};
+
 template <typename T> struct owned_slice {
   using element_type = T;
   using value_type = std::remove_cv_t<T>;
@@ -274,6 +367,9 @@ template <typename T> void swap(owned_ptr<T> &a, owned_ptr<T> &b) noexcept {
 template <typename T> void swap(owned_slice<T> &a, owned_slice<T> &b) noexcept {
   a.swap(b);
 }
+template <typename T> void swap(shared_ptr<T> &a, shared_ptr<T> &b) noexcept {
+  a.swap(b);
+}
 
 namespace detail {
 template <typename T> struct is_slice : std::false_type {};
@@ -295,6 +391,8 @@ using owned_selector =
 
 template <typename T> using owned = detail::owned_selector<T>;
 
+template <typename T> using shared = shared_ptr<std::remove_pointer_t<T>>;
+
 // Implement conversions declared in abi.hpp
 template <typename T>
 inline detail::abi::owned_ptr<T>::owned_ptr(
@@ -332,4 +430,23 @@ inline detail::abi::owned_slice<T>::operator ::vellum::owned_slice<T>()
   return out;
 }
 
+template <typename T>
+inline detail::abi::shared_ptr<T>::shared_ptr(
+    ::vellum::shared_ptr<T> &&other) noexcept
+    : data(other.data), retain(other.retain), release(other.release) {
+  other.data = nullptr;
+  other.retain = nullptr;
+  other.release = nullptr;
+}
+
+template <typename T>
+inline detail::abi::shared_ptr<T>::operator ::vellum::shared_ptr<T>()
+    && noexcept {
+  ::vellum::shared_ptr<T> out(data, retain, release);
+  data = nullptr;
+  retain = nullptr;
+  release = nullptr;
+  return out;
+}
+
 } // namespace vellum
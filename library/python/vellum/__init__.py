@@ -1,4 +1,7 @@
 import ctypes as ct
+import functools
+import sys
+import warnings
 
 def Slice(elem_type):
     class Slice(ct.Structure):
@@ -56,6 +59,9 @@ def Owned(pointer_type):
                 self._freed = True
 
         def __del__(self):
+            # Fields of other values are views, which don't own the pointer
+            if self._b_base_ is not None:
+                return
             try:
                 self.free()
             except Exception:
@@ -69,6 +75,88 @@ def Owned(pointer_type):
 
     return Owned
 
+def Shared(pointer_type):
+    class Shared(ct.Structure):
+        """
+        A reference-counted pointer.
+
+        Each value holds one reference, which is released by `free()` or when it's garbage
+        collected.  `copy()` retains another reference, and passing a value to a function
+        retains the reference given to the function.
+
+        Fields of other values, such as structs and optional values, are views of the reference
+        held by their container, and are only released by `free()`.  Storing a value in a field
+        copies the pointer without retaining it.
+        """
+        POINTER_TYPE = pointer_type
+        REFCOUNT_TYPE = ct.CFUNCTYPE(None, pointer_type)
+
+        _fields_ = [
+            ('data', POINTER_TYPE),
+            ('retain', REFCOUNT_TYPE),
+            ('release', REFCOUNT_TYPE),
+        ]
+
+        @classmethod
+        def from_param(cls, value):
+            # The function is given its own reference, which it releases.
+            value._retain()
+            param = cls(value.data, value.retain, value.release)
+            param._released = True
+            return param
+
+        def _retain(self):
+            if getattr(self, '_released', False):
+                raise ValueError('shared pointer was already released')
+            if self.data and self.retain:
+                self.retain(self.data)
+
+        def copy(self):
+            self._retain()
+            return type(self)(self.data, self.retain, self.release)
+
+        __copy__ = copy
+
+        def free(self):
+            if getattr(self, '_released', False):
+                return
+            try:
+                if self.data and self.release:
+                    self.release(self.data)
+            finally:
+                self.data = None
+                self._released = True
+
+        def __del__(self):
+            if self._b_base_ is not None:
+                return
+            try:
+                self.free()
+            except Exception:
+                pass
+
+    return Shared
+
+def Optional(value_type):
+    class Optional(ct.Structure):
+        VALUE_TYPE = value_type
+
+        def get(self):
+            """
+            The value, or None if it isn't present.
+
+            The value is a view of this optional, so owned and shared values aren't released
+            when it's garbage collected.
+            """
+            return self.value if self.present else None
+
+    fields = [('present', ct.c_bool, None), ('value', value_type, None)]
+    if alignment(value_type) > ct.alignment(value_type):
+        layout(Optional, fields)
+    else:
+        Optional._fields_ = [(name, ty) for name, ty, _ in fields]
+    return Optional
+
 def Closure(ret_type, *arg_types):
     class Closure(ct.Structure):
         FUNCTION_TYPE = ct.CFUNCTYPE(ret_type, ct.c_void_p, *arg_types)
@@ -91,4 +179,164 @@ def Closure(ret_type, *arg_types):
             self.deleter = None
 
         def __del__(self):
+            if self._b_base_ is not None:
+                return
             self.free()
+
+    return Closure
+
+def BorrowedClosure(ret_type, *arg_types):
+    class BorrowedClosure(ct.Structure):
+        """
+        A closure without a deleter, which is only valid for the duration of a call.
+
+        Any Python callable can be passed as a borrowed closure, and is called through a
+        callback that is kept alive until the call returns.
+        """
+        FUNCTION_TYPE = ct.CFUNCTYPE(ret_type, ct.c_void_p, *arg_types)
+
+        _fields_ = [
+            ('call', FUNCTION_TYPE),
+            ('state', ct.c_void_p),
+        ]
+
+        @classmethod
+        def from_param(cls, value):
+            if isinstance(value, cls):
+                return value
+            if not callable(value):
+                raise TypeError('expected a callable, got {!r}'.format(value))
+            callback = cls.FUNCTION_TYPE(lambda state, *args: value(*args))
+            param = cls(callback, None)
+            # ctypes holds the parameter for the call, which holds the callback
+            param._callback = callback
+            return param
+
+        def __call__(self, *args):
+            return self.call(self.state, *args)
+
+    return BorrowedClosure
+
+class Interface(ct.Structure):
+    """
+    A set of methods, passed as a pointer to each method followed by the object implementing them.
+
+    Generated subclasses list the function type of each method, followed by `self`.
+    """
+
+    @classmethod
+    def implement(cls, obj):
+        """
+        Implement the interface with the methods of `obj`.
+
+        The callbacks are kept alive by the interface, which must outlive any use of it.
+        """
+        interface = cls()
+        for name, ty in cls._fields_:
+            if name != 'self':
+                method = getattr(obj, name)
+                setattr(interface, name, ty(lambda _self, *args, method=method: method(*args)))
+        return interface
+
+    def call(self, name, *args):
+        """Call a method with the object implementing the interface."""
+        return getattr(self, name)(self.self, *args)
+
+class int128(ct.Structure):
+    """
+    A 128-bit signed integer, which ctypes doesn't support natively.
+
+    It is stored as 16 bytes in native byte order, and passed like the C type on 64-bit targets.
+    """
+    _fields_ = [('_bytes', ct.c_ubyte * 16)]
+    _vellum_align_ = 16
+    _signed_ = True
+
+    def __init__(self, value=0):
+        super().__init__()
+        self.value = value
+
+    @property
+    def value(self):
+        return int.from_bytes(bytes(self._bytes), sys.byteorder, signed=self._signed_)
+
+    @value.setter
+    def value(self, value):
+        self._bytes[:] = value.to_bytes(16, sys.byteorder, signed=self._signed_)
+
+    def __int__(self):
+        return self.value
+
+class uint128(int128):
+    """A 128-bit unsigned integer, which ctypes doesn't support natively."""
+    _signed_ = False
+
+def alignment(ty):
+    """The alignment of a ctypes type, including any alignment given to `layout`."""
+    if hasattr(ty, '_vellum_align_'):
+        return ty._vellum_align_
+    if issubclass(ty, ct.Array):
+        return alignment(ty._type_)
+    if issubclass(ty, ct.Union):
+        return max((alignment(field[1]) for field in ty._fields_), default=1)
+    return ct.alignment(ty)
+
+def layout(cls, fields, packed=False, align=None):
+    """
+    Set the fields of a structure with explicit alignment.
+
+    ctypes can't align fields or structures beyond their natural alignment, so the structure is
+    packed and padding is inserted instead.  Each field is a tuple of name, type and minimum
+    alignment (or None).  Fields of a subclass are placed after the fields of its base.
+    """
+    padded = []
+    offset = 0
+    struct_align = 1
+    base = cls.__base__
+    if base is not ct.Structure:
+        offset = ct.sizeof(base)
+        struct_align = alignment(base)
+
+    def pad(size):
+        if size:
+            padded.append((f'_padding{len(padded)}_', ct.c_uint8 * size))
+
+    for name, ty, field_align in fields:
+        field_align = max(1 if packed else alignment(ty), field_align or 1)
+        struct_align = max(struct_align, field_align)
+        padding = -offset % field_align
+        pad(padding)
+        padded.append((name, ty))
+        offset += padding + ct.sizeof(ty)
+
+    struct_align = max(struct_align, align or 1)
+    pad(-offset % struct_align)
+
+    cls._layout_ = 'ms'
+    cls._pack_ = 1
+    cls._vellum_align_ = struct_align
+    cls._fields_ = padded
+
+def deprecated(function, name, message=None):
+    """Wrap a foreign function so that calling it emits a DeprecationWarning."""
+    text = f'{name} is deprecated' if message is None else f'{name} is deprecated: {message}'
+
+    @functools.wraps(function)
+    def wrapper(*args):
+        warnings.warn(text, DeprecationWarning, stacklevel=2)
+        return function(*args)
+
+    return wrapper
+
+def non_null(function, name, args):
+    """Wrap a foreign function so that passing None to a non-null pointer argument raises ValueError."""
+
+    @functools.wraps(function)
+    def wrapper(*values):
+        for index, arg in args:
+            value = values[index] if index < len(values) else None
+            if value is None or (isinstance(value, ct._Pointer) and not value):
+                raise ValueError(f'{name}: argument `{arg}` must not be null')
+        return function(*values)
+
+    return wrapper